@@ -6,17 +6,24 @@
 
 use std::net::{SocketAddrV4};
 use std::sync::mpsc::{Sender};
-use std::str::FromStr;
 use std::convert::From;
 use std::time::{Duration, Instant};
 use rosc::{OscPacket, OscMessage, OscType};
 use rosc::encoder;
+use serde_json::Value;
 use std::cmp;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
 extern crate num;
 
 use crate::sensel::*;
 
+use super::music::*;
+use super::template::*;
+use super::expression::{self, Twist};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ArgType {
@@ -51,19 +58,311 @@ pub trait Controller {
     /// process a touch event, outputs OSC
     /// messages to transport layer
     fn touch_start(&mut self, 
-            contact: &contact::Contact, 
+            contact: &contact::TrackedContact, 
             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
                 -> Result<(), &'static str>;
 
     fn touch_move(&mut self, 
-            contact: &contact::Contact, 
+            contact: &contact::TrackedContact, 
             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
                 -> bool;
 
     fn touch_end(&mut self, 
-            contact: &contact::Contact, 
+            contact: &contact::TrackedContact, 
             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
                 -> bool;
+
+    /// called regularly, independently of any contacts, for controllers 
+    /// that keep their own time
+    fn tick(&mut self, 
+            _now: Instant, 
+            _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+    }
+
+    /// process an incoming OSC message, returns true if it was for this controller
+    fn osc_message(&mut self, 
+            _msg: &OscMessage, 
+            _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> bool {
+        false
+    }
+
+    /// current state, e.g. a slider's value, for snapshots and presets, None if
+    /// controller has no state worth keeping
+    fn state(&self) -> Option<Value> {
+        None
+    }
+
+    /// restore state previously returned by state, sending any resulting changes,
+    /// returns true if state was understood
+    fn set_state(&mut self, 
+            _state: &Value, 
+            _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> bool {
+        false
+    }
+
+    /// contact data, beyond position, force and area, the controller needs the
+    /// device to report, e.g. the ellipse for twist
+    fn contact_mask(&self) -> contact::Mask {
+        contact::Mask::empty()
+    }
+}
+
+/// all messages within packet, flattening any bundles
+pub fn osc_messages(packet: &OscPacket) -> Vec<&OscMessage> {
+    match packet {
+        OscPacket::Message(msg) => vec![msg],
+        OscPacket::Bundle(bundle) => bundle.content.iter().flat_map(|p| osc_messages(p)).collect(),
+    }
+}
+
+/// numeric OSC argument as f32
+pub fn osc_f32(arg: &OscType) -> Option<f32> {
+    match *arg {
+        OscType::Int(i) => Some(i as f32),
+        OscType::Long(i) => Some(i as f32),
+        OscType::Float(f) => Some(f),
+        OscType::Double(d) => Some(d as f32),
+        _ => None,
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Rectangular area of the Morph covered by a controller, in the same units
+/// as contact positions
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Region {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Region {
+    /// bounding box of all buffer cells that belong to controller id
+    pub fn from_buffer(buffer: &Vec<Vec<u32>>, id: u32) -> Self {
+        let mut min_x = usize::max_value();
+        let mut min_y = usize::max_value();
+        let mut max_x = 0;
+        let mut max_y = 0;
+
+        for (x, column) in buffer.iter().enumerate() {
+            for (y, cell) in column.iter().enumerate() {
+                if *cell == id {
+                    min_x = cmp::min(min_x, x);
+                    min_y = cmp::min(min_y, y);
+                    max_x = cmp::max(max_x, x);
+                    max_y = cmp::max(max_y, y);
+                }
+            }
+        }
+
+        if min_x > max_x {
+            // controller does not appear in buffer
+            return Region { x: 0.0, y: 0.0, width: 0.0, height: 0.0 };
+        }
+
+        Region {
+            x: min_x as f32,
+            y: min_y as f32,
+            width: (max_x - min_x + 1) as f32,
+            height: (max_y - min_y + 1) as f32,
+        }
+    }
+
+    /// x position relative to region, 0 (left) to 1 (right)
+    pub fn norm_x(&self, x: f32) -> f32 {
+        if self.width <= 0.0 {
+            return 0.0;
+        }
+        num::clamp((x - self.x) / self.width, 0.0, 1.0)
+    }
+
+    /// y position relative to region, 0 (top) to 1 (bottom)
+    pub fn norm_y(&self, y: f32) -> f32 {
+        if self.height <= 0.0 {
+            return 0.0;
+        }
+        num::clamp((y - self.y) / self.height, 0.0, 1.0)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Time within which an incoming message matching one just sent is taken to be
+/// the host echoing it back
+const ECHO_WINDOW: Duration = Duration::from_millis(250);
+
+/// Value updates sent to a controller by the host, on the controller's feedback 
+/// address, which defaults to its own address. Updates are ignored while the 
+/// controller is being touched, as the touch owns its value, and when they echo
+/// a message the controller has just sent, avoiding loops
+#[derive(Debug, Clone)]
+pub struct Feedback {
+    address: String,
+    /// arguments recently sent, and when
+    sent: Vec<(Vec<OscType>, Instant)>,
+    /// contacts currently on controller
+    contacts: Vec<u8>,
+}
+
+impl Feedback {
+    pub fn new(address: String) -> Self {
+        Feedback {
+            address: address,
+            sent: vec![],
+            contacts: vec![],
+        }
+    }
+
+    /// track contacts on controller
+    pub fn contact(&mut self, contact: &contact::TrackedContact) {
+        match contact.state {
+            contact::State::CONTACT_START => {
+                if !self.contacts.contains(&contact.id) {
+                    self.contacts.push(contact.id);
+                }
+            },
+            contact::State::CONTACT_END => {
+                self.contacts.retain(|id| *id != contact.id);
+            },
+            _ => {}
+        }
+    }
+
+    /// record arguments of a message sent by controller
+    pub fn sent(&mut self, args: &Vec<OscType>) {
+        self.sent.retain(|(_, t)| t.elapsed() < ECHO_WINDOW);
+        self.sent.push((args.clone(), Instant::now()));
+    }
+
+    /// address suffix, following the feedback address, and arguments of msg, if it's 
+    /// an update for the controller
+    pub fn receive<'a>(&mut self, msg: &'a OscMessage) -> Option<(&'a str, &'a Vec<OscType>)> {
+        if !msg.addr.starts_with(&self.address) {
+            return None;
+        }
+        let suffix = &msg.addr[self.address.len()..];
+        if !(suffix.is_empty() || suffix.starts_with('/')) {
+            return None;
+        }
+        let args = msg.args.as_ref()?;
+
+        if !self.contacts.is_empty() {
+            return None;
+        }
+
+        self.sent.retain(|(_, t)| t.elapsed() < ECHO_WINDOW);
+        if let Some(i) = self.sent.iter().position(|(sent, _)| sent == args) {
+            // an echo, which is only expected once
+            self.sent.remove(i);
+            return None;
+        }
+
+        Some((suffix, args))
+    }
+}
+
+/// last numeric argument, which carries the value of most controllers' messages
+pub fn osc_last_f32(args: &Vec<OscType>) -> Option<f32> {
+    args.last().and_then(osc_f32)
+}
+
+//-----------------------------------------------------------------------------
+
+/// A release this long after the last movement is not a flick
+const FLICK_MS: u64 = 100;
+
+/// Momentum of a flicked controller. The velocity of the contact moving the controller
+/// is tracked and, once released, the controller keeps moving at that velocity, slowed
+/// by friction, until it stops or is touched again
+#[derive(Debug, Clone)]
+pub struct Inertia {
+    /// fraction of velocity lost each second, 0..1 (default 0.9)
+    friction: f32,
+    /// speed, in value units per second, below which movement stops
+    min_speed: f32,
+    /// velocity, in value units per second, smoothed over recent movement
+    velocity: f32,
+    /// time of last movement, a release long after is not a flick
+    last_move: Option<Instant>,
+    /// time of last tick while coasting, None when not coasting
+    coasting: Option<Instant>,
+}
+
+impl Inertia {
+    /// inertia for a controller with the given range of values, if enabled, None
+    /// if the range is empty, as there is nowhere to move
+    pub fn new(inertia: Option<bool>, friction: Option<f32>, range: f32) -> Option<Self> {
+        if !inertia.unwrap_or(friction.is_some()) || range == 0.0 {
+            return None;
+        }
+        Some(Inertia {
+            friction: num::clamp(friction.unwrap_or(0.9), 0.0, 1.0),
+            min_speed: range.abs() * 0.01,
+            velocity: 0.0,
+            last_move: None,
+            coasting: None,
+        })
+    }
+
+    /// controller touched, stopping any movement
+    pub fn touch(&mut self) {
+        self.velocity = 0.0;
+        self.last_move = Some(Instant::now());
+        self.coasting = None;
+    }
+
+    /// controller moved by contact, at velocity in value units per second, e.g. the
+    /// contact's velocity scaled to the controller's range
+    pub fn moved(&mut self, velocity: f32) {
+        // smooth out jitter between frames
+        self.velocity = 0.5 * self.velocity + 0.5 * velocity;
+        self.last_move = Some(Instant::now());
+    }
+
+    /// contact released, coasting if it was still moving
+    pub fn release(&mut self) {
+        let flick = Duration::from_millis(FLICK_MS);
+        if self.last_move.map_or(true, |t| t.elapsed() > flick) {
+            self.velocity = 0.0;
+        }
+        if self.velocity.abs() >= self.min_speed {
+            self.coasting = Some(Instant::now());
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.velocity = 0.0;
+        self.coasting = None;
+    }
+
+    /// value after moving since last tick, stopping at either end of range, None if
+    /// unchanged
+    pub fn coast(&mut self, now: Instant, value: f32, min: f32, max: f32) -> Option<f32> {
+        let change = self.tick(now)?;
+        let next = num::clamp(value + change, min, max);
+        if next == min || next == max {
+            self.stop();
+        }
+        if next != value { Some(next) } else { None }
+    }
+
+    /// change in value since last tick, None if not moving
+    fn tick(&mut self, now: Instant) -> Option<f32> {
+        let last = self.coasting?;
+        if now <= last {
+            return None;
+        }
+        let dt = now.duration_since(last).as_secs_f32();
+        let change = self.velocity * dt;
+
+        self.velocity *= (1.0 - self.friction).powf(dt);
+        self.coasting = if self.velocity.abs() < self.min_speed { None } else { Some(now) };
+        Some(change)
+    }
 }
 
 //-----------------------------------------------------------------------------
@@ -71,6 +370,7 @@ pub trait Controller {
 const TOUCH_START: i32 = 0;
 const TOUCH_MOVE: i32  = 1;
 const TOUCH_END: i32   = 2;
+const DRUM_CHOKE: i32  = 3;
 
 /// DPad controller
 ///  This is similar to a start Pad, but only generates on/off messages, whose values 
@@ -82,7 +382,6 @@ pub struct DPad {
     /// static OSC message arguments
     args_on: Vec<OscType>,
     args_off: Vec<OscType>,
-    previous_time: Instant,
 }
 
 impl DPad {
@@ -102,7 +401,6 @@ impl DPad {
             address: address,
             args_on: args_on, 
             args_off: args_off, 
-            previous_time: Instant::now(),
         }
     }
     //
@@ -115,71 +413,181 @@ impl Controller for DPad {
 
     /// generate OSC message on start contact
     fn touch_start(&mut self, 
-             contact: &contact::Contact, 
+             contact: &contact::TrackedContact, 
              transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
                 -> Result<(), &'static str> {
        
-        //println!("{} {}", contact.total_force, module_path!());
-        // if contact.total_force <= 20.0 {
-        //     return Ok(());
-        // }
-
-        if self.previous_time.elapsed() > Duration::from_millis(20) {
-
-            let packet = OscPacket::Message(OscMessage {
-                addr: self.address.clone(),
-                args: Some(self.args_on.clone()),
-            });
-            info!("{:?}", packet);
-            let saddr = SocketAddrV4::from_str("127.0.0.1:4000").unwrap();
-            //transport.send((packet, None)).unwrap();
-            transport.send((packet, Some(saddr))).unwrap();
-
-            self.previous_time = Instant::now();
-        }
+        // force threshold and retriggering are handled by the controller's gate
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.address.clone(),
+            args: Some(self.args_on.clone()),
+        });
+        info!("{:?}", packet);
+        transport.send((packet, None)).unwrap();
 
         return Ok(());
     }
 
     fn touch_move(&mut self, 
-        contact: &contact::Contact, 
+        contact: &contact::TrackedContact, 
         transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
 
         return true;
     }
 
     fn touch_end(&mut self, 
-        contact: &contact::Contact, 
+        contact: &contact::TrackedContact, 
         transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
 
-            self.previous_time = Instant::now();
-
             let packet = OscPacket::Message(OscMessage {
                 addr: self.address.clone(),
                 args: Some(self.args_off.clone()),
             });
             info!("{:?}", packet);
-            let saddr = SocketAddrV4::from_str("127.0.0.1:4000").unwrap();
-            //transport.send((packet, None)).unwrap();
-            transport.send((packet, Some(saddr))).unwrap();
+            transport.send((packet, None)).unwrap();
 
         return true;
     }
 }
 
 
+/// Toggle controller
+///  Each press flips the toggle between on and off, sending the static arguments 
+/// followed by the on or off value. The host can set the state by sending either 
+/// value to the toggle's feedback address
+#[derive(Debug, Clone)]
+pub struct Toggle {
+    /// OSC address
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// value sent when toggled on (default 1)
+    on: OscType,
+    /// value sent when toggled off (default 0)
+    off: OscType,
+    /// current state
+    state: bool,
+    feedback: Feedback,
+}
+
+impl Toggle {
+    pub fn new(
+        address: String, args: Vec<ArgType>, 
+        on: Option<ArgType>, off: Option<ArgType>, initial: Option<ArgType>,
+        feedback: Option<String>) -> Self {
+        Toggle {
+            feedback: Feedback::new(feedback.unwrap_or_else(|| address.clone())),
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            on: on.map_or(OscType::Int(1), |x| OscType::from(x)),
+            off: off.map_or(OscType::Int(0), |x| OscType::from(x)),
+            state: initial.map_or(false, |x| f32::from(x) != 0.0),
+        }
+    }
+
+    fn send(&mut self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let mut args = self.args.clone();
+        args.push(if self.state { self.on.clone() } else { self.off.clone() });
+        self.feedback.sent(&args);
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.address.clone(),
+            args: Some(args),
+        });
+        transport.send((packet, None)).unwrap();
+    }
+}
+
+impl Controller for Toggle {
+    fn name(&self) -> &'static str {
+        "toggle"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        self.feedback.contact(contact);
+
+        match contact.state {
+            contact::State::CONTACT_START => {
+                self.state = !self.state;
+                self.send(transport);
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    /// state updates from host, on if the last argument is the on value, otherwise off
+    fn osc_message(&mut self, 
+        msg: &OscMessage, 
+        _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        match self.feedback.receive(msg) {
+            Some(("", args)) => match (osc_last_f32(args), osc_f32(&self.on)) {
+                (Some(value), Some(on)) => {
+                    self.state = value == on;
+                    true
+                },
+                _ => {
+                    self.state = args.last() == Some(&self.on);
+                    true
+                }
+            },
+            _ => false,
+        }
+    }
+
+    fn state(&self) -> Option<Value> {
+        Some(Value::Bool(self.state))
+    }
+
+    fn set_state(&mut self, 
+        state: &Value, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        match state.as_bool() {
+            Some(state) => {
+                if state != self.state {
+                    self.state = state;
+                    self.send(transport);
+                }
+                true
+            },
+            None => false,
+        }
+    }
+}
+
 /// Pad controller
+///  Arguments are given by a template, if one is provided, otherwise the touch phase, 
+/// followed by force and coordinates, if enabled, and then the static arguments
 #[derive(Debug, Clone)]
 pub struct Pad {
     /// OSC address
     address: String,
     /// static OSC message arguments
     args: Vec<OscType>,
-    pressure: bool,
+    /// area of Morph covered by pad
+    region: Region,
+    /// argument template for messages
+    template: Template,
     generate_move: bool,
     generate_end: bool,
-    generate_coords: bool,
-    previous_time: Instant,
 }
 
 impl Pad {
@@ -189,18 +597,45 @@ impl Pad {
             pressure: bool, 
             generate_move: bool,
             generate_end: bool,
-            generate_coords: bool) -> Self {
-        Pad {
+            generate_coords: bool,
+            region: Region,
+            template: Option<Vec<TemplateArg>>) -> Result<Self, &'static str> {
+        let template = match template {
+            Some(template) => Template::new(template)?,
+            None => {
+                if pressure && generate_coords {
+                    Template::from_placeholders(&["$phase", "$force", "$x", "$y", "$args"])
+                }
+                else if generate_coords {
+                    Template::from_placeholders(&["$phase", "$x", "$y", "$args"])
+                }
+                else if pressure {
+                    Template::from_placeholders(&["$phase", "$force", "$args"])
+                }
+                else {
+                    Template::from_placeholders(&["$phase", "$args"])
+                }
+            }
+        };
+
+        Ok(Pad {
             address: address,
             args: args.into_iter().map(|a| OscType::from(a)).collect(),
-            pressure: pressure,
-            generate_coords: generate_coords,
+            region: region,
+            template: template,
             generate_move: generate_move,
             generate_end: generate_end,
-            previous_time: Instant::now(),
-        }
+        })
+    }
+
+    fn send(&mut self, contact: &contact::TrackedContact, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.address.clone(),
+            args: Some(self.template.build(contact, &self.region, &self.args, 0.0)),
+        });
+        info!("{:?}", packet);
+        transport.send((packet, None)).unwrap();
     }
-    //
 }
 
 impl Controller for Pad {
@@ -210,148 +645,46 @@ impl Controller for Pad {
 
     /// generate OSC message on start contact
     fn touch_start(&mut self, 
-             contact: &contact::Contact, 
+             contact: &contact::TrackedContact, 
              transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
                 -> Result<(), &'static str> {
        
-        //println!("{} {}", contact.total_force, module_path!());
-        if contact.total_force <= 20.0 {
-            return Ok(());
-        }
-
-        if self.previous_time.elapsed() > Duration::from_millis(20) {
-            match contact.state {
-                contact::State::CONTACT_START => {
-                    
-                    let args = 
-                        if self.pressure && self.generate_coords {
-                            let mut a = vec![OscType::Int(TOUCH_START),
-                                            OscType::Float(contact.total_force), 
-                                            OscType::Float(contact.x),
-                                            OscType::Float(contact.x)];
-                            a.extend(self.args.iter().cloned());
-                            a
-                        }
-                        else if self.generate_coords {
-                            let mut a = vec![
-                                OscType::Int(TOUCH_START), 
-                                OscType::Float(contact.x), 
-                                OscType::Float(contact.y)];
-                            a.extend(self.args.iter().cloned());
-                            a
-                        }
-                        else if self.pressure {
-                            let mut a = vec![OscType::Int(TOUCH_START), OscType::Float(contact.total_force)];
-                            a.extend(self.args.iter().cloned());
-                            a
-                        }
-                        else {
-                            let mut a = vec![OscType::Int(TOUCH_START)];
-                            a.extend(self.args.clone());
-                            a
-                        };
-
-                        
-
-                    let packet = OscPacket::Message(OscMessage {
-                        addr: self.address.clone(),
-                        args: Some(args),
-                    });
-                    info!("{:?}", packet);
-                    transport.send((packet, None)).unwrap();
-
-                    self.previous_time = Instant::now();
-                },
-                contact::State::CONTACT_MOVE => {
-                    if self.generate_move {
-                        let args = 
-                            if self.pressure && self.generate_coords {
-                                let mut a = vec![OscType::Int(TOUCH_MOVE),
-                                                OscType::Float(contact.total_force), 
-                                                OscType::Float(contact.x),
-                                                OscType::Float(contact.x)];
-                                a.extend(self.args.iter().cloned());
-                                a
-                            }
-                            else if self.generate_coords {
-                                let mut a = vec![
-                                    OscType::Int(TOUCH_MOVE), 
-                                    OscType::Float(contact.x), 
-                                    OscType::Float(contact.y)];
-                                a.extend(self.args.iter().cloned());
-                                a
-                            }
-                            else if self.pressure {
-                                let mut a = vec![OscType::Int(TOUCH_MOVE), OscType::Float(contact.total_force)];
-                                a.extend(self.args.iter().cloned());
-                                a
-                            }
-                            else {
-                                let mut a = vec![OscType::Int(TOUCH_MOVE)];
-                                a.extend(self.args.clone());
-                                a
-                            };
-
-                        let packet = OscPacket::Message(OscMessage {
-                            addr: self.address.clone(),
-                            args: Some(args),
-                        });
-                        transport.send((packet, None)).unwrap();
-                    }
-                },
-                contact::State::CONTACT_END => {
-                    self.previous_time = Instant::now();
-                        // add if generate end
-                        let args = 
-                            if self.pressure && self.generate_coords {
-                                let mut a = vec![OscType::Int(TOUCH_END),
-                                                OscType::Float(contact.total_force), 
-                                                OscType::Float(contact.x),
-                                                OscType::Float(contact.x)];
-                                a.extend(self.args.iter().cloned());
-                                a
-                            }
-                            else if self.generate_coords {
-                                let mut a = vec![OscType::Int(TOUCH_END), OscType::Float(contact.x), OscType::Float(contact.y)];
-                                a.extend(self.args.iter().cloned());
-                                a
-                            }
-                            else if self.pressure {
-                                let mut a = vec![OscType::Int(TOUCH_END), OscType::Float(contact.total_force)];
-                                a.extend(self.args.iter().cloned());
-                                a
-                            }
-                            else {
-                                let mut a = vec![OscType::Int(TOUCH_END)];
-                                a.extend(self.args.clone());
-                                a
-                            };
-
-                        let packet = OscPacket::Message(OscMessage {
-                            addr: self.address.clone(),
-                            args: Some(args),
-                        });
-                        transport.send((packet, None)).unwrap();
-                },
-                // all other states are ignored
-                _ => { }
-            }
+        // force threshold and retriggering are handled by the controller's gate
+        match contact.state {
+            contact::State::CONTACT_START => {
+                self.send(contact, transport);
+            },
+            contact::State::CONTACT_MOVE => {
+                if self.generate_move {
+                    self.send(contact, transport);
+                }
+            },
+            contact::State::CONTACT_END => {
+                // add if generate end
+                self.send(contact, transport);
+            },
+            // all other states are ignored
+            _ => { }
         }
         return Ok(());
     }
 
     fn touch_move(&mut self, 
-        contact: &contact::Contact, 
+        contact: &contact::TrackedContact, 
         transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
 
-        return true;
+        self.touch_start(contact, transport).is_ok()
     }
 
     fn touch_end(&mut self, 
-        contact: &contact::Contact, 
+        contact: &contact::TrackedContact, 
         transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
 
-        return true;
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn contact_mask(&self) -> contact::Mask {
+        self.template.mask()
     }
 }
 
@@ -369,25 +702,43 @@ pub struct VSlider {
     incr: f32,
     /// current value of slider
     value: f32,
-    /// last y position
-    last_y: i32,
+    feedback: Feedback,
+    /// keeps moving after a flick, if enabled
+    inertia: Option<Inertia>,
 }
 
 impl VSlider {
     pub fn new(
         address: String, args: Vec<ArgType>, 
         min: Option<ArgType>, max: Option<ArgType>,
-        initial: Option<ArgType>, incr: Option<ArgType>) -> Self {
+        initial: Option<ArgType>, incr: Option<ArgType>,
+        feedback: Option<String>, inertia: Option<bool>, friction: Option<f32>) -> Self {
+        let min = min.map_or(0.0, |x| f32::from(x));
+        let max = max.map_or(127.0, |x| f32::from(x));
         VSlider {
+            feedback: Feedback::new(feedback.unwrap_or_else(|| address.clone())),
             address: address,
             args: args.into_iter().map(|a| OscType::from(a)).collect(),
-            min: min.map_or(0.0, |x| f32::from(x)),
-            max: max.map_or(127.0, |x| f32::from(x)),
+            min: min,
+            max: max,
             incr: incr.map_or(1.0, |x| f32::from(x)),
             value: initial.map_or(0.0, |x| f32::from(x)),
-            last_y: 0,
+            inertia: Inertia::new(inertia, friction, max - min),
         }
     }
+
+    fn send(&mut self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        // build OSC argument list
+        let mut args = self.args.clone();
+        args.push(OscType::Float(self.value));
+        self.feedback.sent(&args);
+        // create OSC packet and send
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.address.clone(),
+            args: Some(args),
+        });
+        transport.send((packet, None)).unwrap();
+    }
 }
 
 impl Controller for VSlider {
@@ -396,41 +747,39 @@ impl Controller for VSlider {
     }
 
     fn touch_start(&mut self, 
-             contact: &contact::Contact, 
+             contact: &contact::TrackedContact, 
              transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
                 -> Result<(), &'static str> {
+        self.feedback.contact(contact);
 
         match contact.state {
             contact::State::CONTACT_START => {
-                // set touch start position
-                self.last_y = contact.y as i32;
+                if let Some(inertia) = self.inertia.as_mut() {
+                    inertia.touch();
+                }
                 Ok(())
             },
             contact::State::CONTACT_END => {
-                // reset on touch start and so nothing to do here
+                // reset on touch start, but may keep moving if flicked
+                if let Some(inertia) = self.inertia.as_mut() {
+                    inertia.release();
+                }
                 Ok(())
             },
             contact::State::CONTACT_MOVE => {
-                let y = contact.y as i32;
+                let last_y = contact.previous().map_or(contact.y, |last| last.y);
 
                 // determine upwards or downwards movement (or no movement)
-                let movement = (self.last_y - y) as f32 * self.incr;
-                
-                // update state to reflect current touch position
-                self.last_y = y;
+                let movement = (last_y as i32 - contact.y as i32) as f32 * self.incr;
         
                 // only send message if there was some movement
                 if movement != 0.0 {
-                    self.value = num::clamp(self.value + movement, self.min, self.max);
-                    // build OSC argument list
-                    let mut args = self.args.clone();
-                    args.push(OscType::Float(self.value));
-                    // create OSC packet and send
-                    let packet = OscPacket::Message(OscMessage {
-                        addr: self.address.clone(),
-                        args: Some(args),
-                    });
-                    transport.send((packet, None)).unwrap();
+                    let value = num::clamp(self.value + movement, self.min, self.max);
+                    if let Some(inertia) = self.inertia.as_mut() {
+                        inertia.moved(-contact.velocity_y * self.incr);
+                    }
+                    self.value = value;
+                    self.send(transport);
                 }
                 
                 Ok(())    
@@ -442,17 +791,64 @@ impl Controller for VSlider {
     }
 
     fn touch_move(&mut self, 
-        contact: &contact::Contact, 
+        contact: &contact::TrackedContact, 
         transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
 
-        return true;
+        self.touch_start(contact, transport).is_ok()
     }
 
     fn touch_end(&mut self, 
-        contact: &contact::Contact, 
+        contact: &contact::TrackedContact, 
         transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
 
-        return true;
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    /// keep moving after a flick
+    fn tick(&mut self, 
+        now: Instant, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let (value, min, max) = (self.value, self.min, self.max);
+        if let Some(value) = self.inertia.as_mut().and_then(|i| i.coast(now, value, min, max)) {
+            self.value = value;
+            self.send(transport);
+        }
+    }
+
+    /// value updates from host
+    fn osc_message(&mut self, 
+        msg: &OscMessage, 
+        _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        match self.feedback.receive(msg) {
+            Some(("", args)) => match osc_last_f32(args) {
+                Some(value) => {
+                    self.value = num::clamp(value, self.min, self.max);
+                    true
+                },
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn state(&self) -> Option<Value> {
+        Some(Value::from(self.value as f64))
+    }
+
+    fn set_state(&mut self, 
+        state: &Value, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        match state.as_f64() {
+            Some(value) => {
+                let value = num::clamp(value as f32, self.min, self.max);
+                if value != self.value {
+                    self.value = value;
+                    self.send(transport);
+                }
+                true
+            },
+            None => false,
+        }
     }
 }
 
@@ -470,25 +866,43 @@ pub struct HSlider {
     incr: f32,
     /// current value of slider
     value: f32,
-    /// last x position
-    last_x: i32,
+    feedback: Feedback,
+    /// keeps moving after a flick, if enabled
+    inertia: Option<Inertia>,
 }
 
 impl HSlider {
     pub fn new(
         address: String, args: Vec<ArgType>, 
         min: Option<ArgType>, max: Option<ArgType>,
-        initial: Option<ArgType>, incr: Option<ArgType>) -> Self {
+        initial: Option<ArgType>, incr: Option<ArgType>,
+        feedback: Option<String>, inertia: Option<bool>, friction: Option<f32>) -> Self {
+        let min = min.map_or(0.0, |x| f32::from(x));
+        let max = max.map_or(127.0, |x| f32::from(x));
         HSlider {
+            feedback: Feedback::new(feedback.unwrap_or_else(|| address.clone())),
             address: address,
             args: args.into_iter().map(|a| OscType::from(a)).collect(),
-            min: min.map_or(0.0, |x| f32::from(x)),
-            max: max.map_or(127.0, |x| f32::from(x)),
+            min: min,
+            max: max,
             incr: incr.map_or(1.0, |x| f32::from(x)),
             value: initial.map_or(0.0, |x| f32::from(x)),
-            last_x: 0,
+            inertia: Inertia::new(inertia, friction, max - min),
         }
     }
+
+    fn send(&mut self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        // build OSC argument list
+        let mut args = self.args.clone();
+        args.push(OscType::Float(self.value));
+        self.feedback.sent(&args);
+        // create OSC packet and send
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.address.clone(),
+            args: Some(args),
+        });
+        transport.send((packet, None)).unwrap();
+    }
 }
 
 impl Controller for HSlider {
@@ -497,40 +911,39 @@ impl Controller for HSlider {
     }
 
     fn touch_start(&mut self, 
-             contact: &contact::Contact, 
+             contact: &contact::TrackedContact, 
              transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
                 -> Result<(), &'static str> {
+        self.feedback.contact(contact);
+
                match contact.state {
             contact::State::CONTACT_START => {
-                // set touch start position
-                self.last_x = contact.y as i32;
+                if let Some(inertia) = self.inertia.as_mut() {
+                    inertia.touch();
+                }
                 Ok(())
             },
             contact::State::CONTACT_END => {
-                // reset on touch start and so nothing to do here
+                // reset on touch start, but may keep moving if flicked
+                if let Some(inertia) = self.inertia.as_mut() {
+                    inertia.release();
+                }
                 Ok(())
             },
             contact::State::CONTACT_MOVE => {
-                let x = contact.x as i32;
+                let last_x = contact.previous().map_or(contact.x, |last| last.x);
                 
                 // determine left or right movement (or no movement)
-                let movement = (self.last_x - x) as f32 * self.incr;
-
-                // update state to reflect current touch position
-                self.last_x = x;
+                let movement = (last_x as i32 - contact.x as i32) as f32 * self.incr;
                 
                 // only send message if there was some movement
                 if movement != 0.0 {
-                    self.value = num::clamp(self.value + movement, self.min, self.max);
-                    // build OSC argument list
-                    let mut args = self.args.clone();
-                    args.push(OscType::Float(self.value));
-                    // create OSC packet and send
-                    let packet = OscPacket::Message(OscMessage {
-                        addr: self.address.clone(),
-                        args: Some(args),
-                    });
-                    transport.send((packet, None)).unwrap();
+                    let value = num::clamp(self.value + movement, self.min, self.max);
+                    if let Some(inertia) = self.inertia.as_mut() {
+                        inertia.moved(-contact.velocity_x * self.incr);
+                    }
+                    self.value = value;
+                    self.send(transport);
                 }
                 
                 Ok(())    
@@ -542,17 +955,64 @@ impl Controller for HSlider {
     }
 
     fn touch_move(&mut self, 
-        contact: &contact::Contact, 
+        contact: &contact::TrackedContact, 
         transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
 
-        return true;
+        self.touch_start(contact, transport).is_ok()
     }
 
     fn touch_end(&mut self, 
-        contact: &contact::Contact, 
+        contact: &contact::TrackedContact, 
         transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
 
-        return true;
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    /// keep moving after a flick
+    fn tick(&mut self, 
+        now: Instant, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let (value, min, max) = (self.value, self.min, self.max);
+        if let Some(value) = self.inertia.as_mut().and_then(|i| i.coast(now, value, min, max)) {
+            self.value = value;
+            self.send(transport);
+        }
+    }
+
+    /// value updates from host
+    fn osc_message(&mut self, 
+        msg: &OscMessage, 
+        _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        match self.feedback.receive(msg) {
+            Some(("", args)) => match osc_last_f32(args) {
+                Some(value) => {
+                    self.value = num::clamp(value, self.min, self.max);
+                    true
+                },
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn state(&self) -> Option<Value> {
+        Some(Value::from(self.value as f64))
+    }
+
+    fn set_state(&mut self, 
+        state: &Value, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        match state.as_f64() {
+            Some(value) => {
+                let value = num::clamp(value as f32, self.min, self.max);
+                if value != self.value {
+                    self.value = value;
+                    self.send(transport);
+                }
+                true
+            },
+            None => false,
+        }
     }
 }
 
@@ -579,23 +1039,2430 @@ impl Controller for Endless {
     }
 
     fn touch_start(&mut self, 
-             contact: &contact::Contact, 
+             contact: &contact::TrackedContact, 
              transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
                 -> Result<(), &'static str> {
         Ok(())
     }
 
     fn touch_move(&mut self, 
-        contact: &contact::Contact, 
+        contact: &contact::TrackedContact, 
         transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
 
         return true;
     }
 
     fn touch_end(&mut self, 
-        contact: &contact::Contact, 
+        contact: &contact::TrackedContact, 
         transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
 
         return true;
     }
-}
\ No newline at end of file
+}
+//-----------------------------------------------------------------------------
+
+const NOTE_ON: &'static str = "note_on";
+const NOTE_OFF: &'static str = "note_off";
+const PRESSURE: &'static str = "pressure";
+const PITCH_BEND: &'static str = "pitch_bend";
+const SLIDE: &'static str = "slide";
+
+/// A single key of a keyboard
+#[derive(Debug, Clone, Copy)]
+struct Key {
+    note: u8,
+    /// area of Morph covered by key
+    area: Region,
+}
+
+/// Keyboard controller
+///  Maps the controller's region to a range of keys, either left to right, taken from a 
+/// scale, or as an isomorphic grid. Each finger is allocated its own channel, MPE style, 
+/// on which note on/off, pressure, pitch bend (relative to the key centre) and slide 
+/// (vertical position within the key) are sent
+#[derive(Debug, Clone)]
+pub struct Keyboard {
+    /// OSC address, messages are sent to address/note_on, address/pressure, and so on
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// area of Morph covered by keyboard
+    region: Region,
+    /// note of each key, left to right, when not a grid
+    keys: Vec<u8>,
+    /// isomorphic layout of keys, if any
+    grid: Option<Isomorphic>,
+    /// pitch bend, in semitones, for a movement of one key width (default 1)
+    bend_range: f32,
+    /// force that maps to full velocity and pressure (default 2000)
+    max_force: f32,
+    /// channel allocated to each sounding contact
+    voices: VoiceAllocator,
+    /// key held by each sounding contact
+    held: HashMap<u8, Key>,
+}
+
+impl Keyboard {
+    pub fn new(
+        address: String, args: Vec<ArgType>, region: Region,
+        low_note: Option<u8>, high_note: Option<u8>,
+        root: Option<u8>, scale: Option<ScaleType>, grid: Option<Isomorphic>,
+        channels: Option<u8>, bend_range: Option<f32>, max_force: Option<f32>) -> Result<Self, &'static str> {
+
+        let low_note = low_note.unwrap_or(48);
+        let high_note = high_note.unwrap_or(72);
+        let scale = Scale::new(root.unwrap_or(low_note), scale)?;
+        let keys = scale.notes(low_note, high_note);
+
+        if grid.is_none() && keys.len() == 0 {
+            return Err("keyboard has no keys in its note range");
+        }
+
+        Ok(Keyboard {
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            region: region,
+            keys: keys,
+            grid: grid,
+            bend_range: bend_range.unwrap_or(1.0),
+            max_force: max_force.unwrap_or(2000.0),
+            voices: VoiceAllocator::lower_zone(channels)?,
+            held: HashMap::new(),
+        })
+    }
+
+    /// key under position, None if position falls between cells of a grid
+    fn key(&self, x: f32, y: f32) -> Option<Key> {
+        match &self.grid {
+            Some(grid) => {
+                grid.cell(&self.region, x, y).and_then(|(col, row)| {
+                    let note = grid.note(col, row);
+                    if note < 0 || note > 127 {
+                        return None;
+                    }
+                    Some(Key {
+                        note: note as u8,
+                        area: grid.cell_region(&self.region, col, row),
+                    })
+                })
+            },
+            None => {
+                let width = self.region.width / self.keys.len() as f32;
+                let key = (self.region.norm_x(x) * self.keys.len() as f32) as usize;
+                let key = cmp::min(key, self.keys.len() - 1);
+                Some(Key {
+                    note: self.keys[key],
+                    area: Region { 
+                        x: self.region.x + key as f32 * width, 
+                        width: width, 
+                        ..self.region 
+                    },
+                })
+            }
+        }
+    }
+
+    /// pitch bend, in semitones, of x position relative to centre of key
+    fn bend(&self, key: &Key, x: f32) -> f32 {
+        if key.area.width <= 0.0 {
+            return 0.0;
+        }
+        let centre = key.area.x + key.area.width * 0.5;
+        (x - centre) / key.area.width * self.bend_range
+    }
+
+    fn send(&self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>, msg: &str, mut args: Vec<OscType>) {
+        args.extend(self.args.iter().cloned());
+        let packet = OscPacket::Message(OscMessage {
+            addr: format!("{}/{}", self.address, msg),
+            args: Some(args),
+        });
+        info!("{:?}", packet);
+        transport.send((packet, None)).unwrap();
+    }
+
+    /// send per note expression for contact
+    fn expression(&self, 
+        channel: u8, key: &Key,
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let channel = OscType::Int(channel as i32);
+        let pressure = num::clamp(contact.total_force / self.max_force, 0.0, 1.0);
+        // slide increases as the finger moves up the key
+        let slide = 1.0 - key.area.norm_y(contact.y);
+
+        self.send(transport, PRESSURE, vec![channel.clone(), OscType::Float(pressure)]);
+        self.send(transport, PITCH_BEND, vec![channel.clone(), OscType::Float(self.bend(key, contact.x))]);
+        self.send(transport, SLIDE, vec![channel, OscType::Float(slide)]);
+    }
+}
+
+impl Controller for Keyboard {
+    fn name(&self) -> &'static str {
+        "keyboard"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        match contact.state {
+            contact::State::CONTACT_START => {
+                let key = match self.key(contact.x, contact.y) {
+                    Some(key) => key,
+                    None => return Ok(()),
+                };
+                let channel = match self.voices.allocate(contact.id) {
+                    Some(channel) => channel,
+                    None => return Err("keyboard has no free channels"),
+                };
+                self.held.insert(contact.id, key);
+
+                let velocity = num::clamp(
+                    (contact.total_force / self.max_force * 127.0).round() as i32, 1, 127);
+                self.send(transport, NOTE_ON, vec![
+                    OscType::Int(channel as i32), 
+                    OscType::Int(key.note as i32), 
+                    OscType::Int(velocity)]);
+                self.expression(channel, &key, contact, transport);
+                Ok(())
+            },
+            contact::State::CONTACT_MOVE => {
+                if let (Some(channel), Some(key)) = (self.voices.channel(contact.id), self.held.get(&contact.id)) {
+                    self.expression(channel, key, contact, transport);
+                }
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                if let (Some(channel), Some(key)) = (self.voices.release(contact.id), self.held.remove(&contact.id)) {
+                    self.send(transport, NOTE_OFF, vec![
+                        OscType::Int(channel as i32), 
+                        OscType::Int(key.note as i32), 
+                        OscType::Int(0)]);
+                }
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Identity of a drum, distinct for each drum even if they send the same messages
+type DrumToken = Rc<()>;
+
+/// Drum that last sounded in a choke group
+#[derive(Debug, Clone)]
+pub struct Choke {
+    token: DrumToken,
+    /// message that chokes the drum
+    message: OscMessage,
+    /// where the drum sent its strike, so its choke goes the same way
+    transport: Sender<(OscPacket, Option<SocketAddrV4>)>,
+}
+
+/// Drum that last sounded in each choke group, shared between all drums of an
+/// interface
+pub type ChokeGroups = Rc<RefCell<HashMap<u32, Choke>>>;
+
+/// Strike of a contact still within its onset window
+#[derive(Debug, Clone, Copy)]
+struct Onset {
+    /// number of frames seen so far
+    frames: u32,
+    /// peak force seen so far
+    peak_force: f32,
+    /// peak per frame force increase seen so far
+    peak_rise: f32,
+}
+
+/// Drum controller
+///  Unlike a Pad, the strike velocity is estimated over the first few frames of a 
+/// contact, rather than from the very first frame, which catches only the start of a hit.
+/// After the strike, pressure is sent as aftertouch until the contact ends. Drums can be
+/// placed in a choke group, where striking one drum chokes the one previously sounding
+#[derive(Debug, Clone)]
+pub struct Drum {
+    /// OSC address
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// number of frames over which strike velocity is estimated (default 3)
+    window: u32,
+    /// force that maps to full velocity and pressure (default 2000)
+    max_force: f32,
+    /// shaping applied to strike velocity
+    curve: VelocityCurve,
+    /// choke group drum belongs to, if any
+    choke_group: Option<u32>,
+    chokes: ChokeGroups,
+    token: DrumToken,
+    /// contacts that have not yet struck
+    onsets: HashMap<u8, Onset>,
+}
+
+impl Drum {
+    pub fn new(
+        address: String, args: Vec<ArgType>,
+        window: Option<u32>, max_force: Option<f32>, curve: Option<String>,
+        choke_group: Option<u32>, chokes: ChokeGroups) -> Result<Self, &'static str> {
+        Ok(Drum {
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            window: cmp::max(1, window.unwrap_or(3)),
+            max_force: max_force.unwrap_or(2000.0),
+            curve: VelocityCurve::new(curve)?,
+            choke_group: choke_group,
+            chokes: chokes,
+            token: Rc::new(()),
+            onsets: HashMap::new(),
+        })
+    }
+
+    fn message(&self, phase: i32, value: OscType) -> OscMessage {
+        let mut args = vec![OscType::Int(phase), value];
+        args.extend(self.args.iter().cloned());
+        OscMessage {
+            addr: self.address.clone(),
+            args: Some(args),
+        }
+    }
+
+    /// update onset with contact's current force
+    fn observe(onset: &mut Onset, contact: &contact::TrackedContact) {
+        onset.frames += 1;
+        onset.peak_force = onset.peak_force.max(contact.total_force);
+        if let Some(delta) = contact.delta {
+            onset.peak_rise = onset.peak_rise.max(delta.force);
+        }
+    }
+
+    /// send strike, choking whichever drum last sounded in our group
+    fn strike(&mut self, onset: Onset, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        // the rate of rise, when deltas are enabled, catches a hard hit whose force has
+        // not yet peaked by the end of the window
+        let strength = onset.peak_force.max(onset.peak_rise * self.window as f32) / self.max_force;
+        let velocity = self.curve.velocity(strength);
+
+        if let Some(group) = self.choke_group {
+            let choke = Choke {
+                token: self.token.clone(),
+                message: self.message(DRUM_CHOKE, OscType::Int(0)),
+                transport: transport.clone(),
+            };
+            let previous = self.chokes.borrow_mut().insert(group, choke);
+            if let Some(previous) = previous {
+                // compared by identity, as two drums may send the same messages
+                if !Rc::ptr_eq(&previous.token, &self.token) {
+                    previous.transport.send((OscPacket::Message(previous.message), None)).unwrap();
+                }
+            }
+        }
+
+        let packet = OscPacket::Message(self.message(TOUCH_START, OscType::Int(velocity)));
+        info!("{:?}", packet);
+        transport.send((packet, None)).unwrap();
+    }
+}
+
+impl Controller for Drum {
+    fn name(&self) -> &'static str {
+        "drum"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        match contact.state {
+            contact::State::CONTACT_START | contact::State::CONTACT_MOVE => {
+                let onset = match self.onsets.get_mut(&contact.id) {
+                    Some(onset) => {
+                        Drum::observe(onset, contact);
+                        Some(*onset)
+                    },
+                    None if contact.state == contact::State::CONTACT_START => {
+                        let mut onset = Onset { frames: 0, peak_force: 0.0, peak_rise: 0.0 };
+                        Drum::observe(&mut onset, contact);
+                        self.onsets.insert(contact.id, onset);
+                        Some(onset)
+                    },
+                    None => None,
+                };
+
+                match onset {
+                    Some(onset) if onset.frames >= self.window => {
+                        self.onsets.remove(&contact.id);
+                        self.strike(onset, transport);
+                    },
+                    Some(_) => {},
+                    None => {
+                        // already struck, so send aftertouch
+                        let pressure = num::clamp(contact.total_force / self.max_force, 0.0, 1.0);
+                        let packet = OscPacket::Message(self.message(TOUCH_MOVE, OscType::Float(pressure)));
+                        transport.send((packet, None)).unwrap();
+                    },
+                }
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                // a contact that ends within its window still strikes, with what we have seen
+                if let Some(onset) = self.onsets.remove(&contact.id) {
+                    self.strike(onset, transport);
+                }
+                let packet = OscPacket::Message(self.message(TOUCH_END, OscType::Float(0.0)));
+                transport.send((packet, None)).unwrap();
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    /// force deltas sharpen the strike estimate
+    fn contact_mask(&self) -> contact::Mask {
+        contact::Mask::DELTAS
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+const PLAYHEAD: &'static str = "playhead";
+const CELL: &'static str = "cell";
+
+/// Step sequencer controller
+///  The region is divided into a grid of cells, one column per step and one row per
+/// track. Tapping a cell toggles it, with the pressure of the tap setting the step's 
+/// velocity. An internal clock advances the playhead, sending a message for each active
+/// step of the current column, along with the playhead position so that a visual client
+/// can follow along. Tempo can be changed with address/tempo and the clock stopped and
+/// started with address/stop and address/start.
+///  Steps are sent from tick, so each is late by at most the time between ticks, a
+/// single frame of the Morph, but are scheduled on a fixed grid from the clock's start,
+/// so this lateness never accumulates, whatever the rate of ticks
+#[derive(Debug, Clone)]
+pub struct Sequencer {
+    /// OSC address
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// area of Morph covered by sequencer
+    region: Region,
+    /// number of steps
+    columns: usize,
+    /// number of tracks
+    rows: usize,
+    /// velocity, 0..1, of each cell, None if cell is off
+    cells: Vec<Option<f32>>,
+    /// beats per minute (default 120)
+    tempo: f32,
+    /// steps in a single beat (default 4)
+    steps_per_beat: u32,
+    /// force that maps to full velocity (default 2000)
+    max_force: f32,
+    /// column that will sound at the next step
+    playhead: usize,
+    /// time the next step is due, None if the clock has yet to start
+    next_step: Option<Instant>,
+    running: bool,
+    /// cell being set by each contact
+    touches: HashMap<u8, usize>,
+}
+
+impl Sequencer {
+    pub fn new(
+        address: String, args: Vec<ArgType>, region: Region,
+        columns: Option<usize>, rows: Option<usize>,
+        tempo: Option<f32>, steps_per_beat: Option<u32>, max_force: Option<f32>) -> Self {
+        let columns = cmp::max(1, columns.unwrap_or(16));
+        let rows = cmp::max(1, rows.unwrap_or(1));
+        Sequencer {
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            region: region,
+            columns: columns,
+            rows: rows,
+            cells: vec![None; columns * rows],
+            tempo: tempo.unwrap_or(120.0),
+            steps_per_beat: cmp::max(1, steps_per_beat.unwrap_or(4)),
+            max_force: max_force.unwrap_or(2000.0),
+            playhead: 0,
+            next_step: None,
+            running: true,
+            touches: HashMap::new(),
+        }
+    }
+
+    fn step_duration(&self) -> Duration {
+        let seconds = 60.0 / (self.tempo.max(1.0) * self.steps_per_beat as f32);
+        Duration::from_micros(cmp::max(1, (seconds * 1_000_000.0) as u64))
+    }
+
+    /// index of cell under contact
+    fn cell(&self, contact: &contact::TrackedContact) -> usize {
+        let column = cmp::min((self.region.norm_x(contact.x) * self.columns as f32) as usize, self.columns - 1);
+        let row = cmp::min((self.region.norm_y(contact.y) * self.rows as f32) as usize, self.rows - 1);
+        row * self.columns + column
+    }
+
+    fn send(&self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>, addr: String, mut args: Vec<OscType>) {
+        args.extend(self.args.iter().cloned());
+        let packet = OscPacket::Message(OscMessage {
+            addr: addr,
+            args: Some(args),
+        });
+        transport.send((packet, None)).unwrap();
+    }
+
+    /// let a visual client know the state of a cell
+    fn send_cell(&self, cell: usize, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let velocity = self.cells[cell].unwrap_or(0.0);
+        self.send(
+            transport, 
+            format!("{}/{}", self.address, CELL), 
+            vec![
+                OscType::Int((cell % self.columns) as i32), 
+                OscType::Int((cell / self.columns) as i32),
+                OscType::Float(velocity)]);
+    }
+
+    /// sound active cells of current column and advance playhead
+    fn step(&mut self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        self.send(
+            transport, 
+            format!("{}/{}", self.address, PLAYHEAD), 
+            vec![OscType::Int(self.playhead as i32)]);
+
+        for row in 0..self.rows {
+            if let Some(velocity) = self.cells[row * self.columns + self.playhead] {
+                self.send(
+                    transport, 
+                    self.address.clone(), 
+                    vec![OscType::Int(row as i32), OscType::Float(velocity)]);
+            }
+        }
+
+        self.playhead = (self.playhead + 1) % self.columns;
+    }
+}
+
+impl Controller for Sequencer {
+    fn name(&self) -> &'static str {
+        "sequencer"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        match contact.state {
+            contact::State::CONTACT_START => {
+                let cell = self.cell(contact);
+                if self.cells[cell].is_some() {
+                    self.cells[cell] = None;
+                }
+                else {
+                    let velocity = num::clamp(contact.total_force / self.max_force, 0.0, 1.0);
+                    self.cells[cell] = Some(velocity);
+                    self.touches.insert(contact.id, cell);
+                }
+                self.send_cell(cell, transport);
+                Ok(())
+            },
+            contact::State::CONTACT_MOVE => {
+                // pressing harder, while setting a step, increases its velocity
+                if let Some(cell) = self.touches.get(&contact.id).cloned() {
+                    let velocity = num::clamp(contact.total_force / self.max_force, 0.0, 1.0);
+                    if let Some(current) = self.cells[cell] {
+                        if velocity > current {
+                            self.cells[cell] = Some(velocity);
+                            self.send_cell(cell, transport);
+                        }
+                    }
+                }
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                self.touches.remove(&contact.id);
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn tick(&mut self, 
+        now: Instant, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        if !self.running {
+            return;
+        }
+
+        let next_step = self.next_step.unwrap_or(now);
+        if now >= next_step {
+            // if we have fallen more than a step behind, skip the missed steps rather
+            // than sending a burst of them, keeping the playhead on time
+            let duration = self.step_duration();
+            let missed = (now.duration_since(next_step).as_micros() / duration.as_micros()) as u32;
+            self.playhead = (self.playhead + missed as usize) % self.columns;
+            self.step(transport);
+            self.next_step = Some(next_step + duration * (missed + 1));
+        }
+    }
+
+    fn osc_message(&mut self, 
+        msg: &OscMessage, 
+        _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        if !msg.addr.starts_with(&self.address) {
+            return false;
+        }
+
+        match &msg.addr[self.address.len()..] {
+            "/tempo" => {
+                let tempo = msg.args.as_ref().and_then(|args| args.first()).and_then(osc_f32);
+                match tempo {
+                    Some(tempo) if tempo > 0.0 => {
+                        self.tempo = tempo;
+                        true
+                    },
+                    _ => false,
+                }
+            },
+            "/start" => {
+                self.running = true;
+                self.playhead = 0;
+                self.next_step = None;
+                true
+            },
+            "/stop" => {
+                self.running = false;
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// cell velocities, null if off, and tempo
+    fn state(&self) -> Option<Value> {
+        let mut state = serde_json::Map::new();
+        state.insert(
+            "cells".to_string(), 
+            Value::Array(self.cells.iter().map(|c| c.map_or(Value::Null, |v| Value::from(v as f64))).collect()));
+        state.insert("tempo".to_string(), Value::from(self.tempo as f64));
+        Some(Value::Object(state))
+    }
+
+    fn set_state(&mut self, 
+        state: &Value, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let state = match state.as_object() {
+            Some(state) => state,
+            None => return false,
+        };
+
+        if let Some(tempo) = state.get("tempo").and_then(|t| t.as_f64()) {
+            if tempo > 0.0 {
+                self.tempo = tempo as f32;
+            }
+        }
+
+        if let Some(cells) = state.get("cells").and_then(|c| c.as_array()) {
+            for cell in 0..cmp::min(cells.len(), self.cells.len()) {
+                let velocity = cells[cell].as_f64().map(|v| num::clamp(v as f32, 0.0, 1.0));
+                if velocity != self.cells[cell] {
+                    self.cells[cell] = velocity;
+                    self.send_cell(cell, transport);
+                }
+            }
+        }
+        true
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+const PITCH: &'static str = "pitch";
+const TIMBRE: &'static str = "timbre";
+const AMPLITUDE: &'static str = "amplitude";
+
+/// change in target pitch, in semitones, treated as moving to a new note
+const GLIDE_THRESHOLD: f32 = 0.5;
+
+/// A sounding finger of a pitch surface
+#[derive(Debug, Clone, Copy)]
+struct SurfaceVoice {
+    channel: u8,
+    /// pitch finger is asking for, after rounding
+    target: f32,
+    /// pitch currently being sent
+    pitch: f32,
+    /// pitch and time of the start of a glide in progress, if any
+    glide: Option<(f32, Instant)>,
+}
+
+/// Pitch surface controller
+///  Continuum style, x maps to continuous pitch across the region, optionally rounded
+/// toward the notes of a scale with a given strength. When rounding moves a finger on 
+/// to a new note, pitch glides there over glide time. Vertical position controls timbre 
+/// and pressure amplitude, with each finger allocated its own channel
+#[derive(Debug, Clone)]
+pub struct PitchSurface {
+    /// OSC address, messages are sent to address/note_on, address/pitch, and so on
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// area of Morph covered by surface
+    region: Region,
+    /// pitch at left edge of surface (default 48)
+    low_note: f32,
+    /// pitch at right edge of surface (default 72)
+    high_note: f32,
+    scale: Scale,
+    /// 0 for continuous pitch to 1 for pitch snapped to scale notes (default 0)
+    rounding: f32,
+    /// time to glide between notes (default 0)
+    glide: Duration,
+    /// force that maps to full amplitude (default 2000)
+    max_force: f32,
+    voices: VoiceAllocator,
+    sounding: HashMap<u8, SurfaceVoice>,
+}
+
+impl PitchSurface {
+    pub fn new(
+        address: String, args: Vec<ArgType>, region: Region,
+        low_note: Option<u8>, high_note: Option<u8>,
+        root: Option<u8>, scale: Option<ScaleType>, rounding: Option<RoundingType>,
+        glide_ms: Option<u64>, channels: Option<u8>, max_force: Option<f32>) -> Result<Self, &'static str> {
+        let low_note = low_note.unwrap_or(48);
+        Ok(PitchSurface {
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            region: region,
+            low_note: low_note as f32,
+            high_note: high_note.unwrap_or(72) as f32,
+            scale: Scale::new(root.unwrap_or(low_note), scale)?,
+            rounding: rounding_strength(rounding)?,
+            glide: Duration::from_millis(glide_ms.unwrap_or(0)),
+            max_force: max_force.unwrap_or(2000.0),
+            voices: VoiceAllocator::lower_zone(channels)?,
+            sounding: HashMap::new(),
+        })
+    }
+
+    /// pitch under x position, after rounding
+    fn target(&self, x: f32) -> f32 {
+        let pitch = self.low_note + self.region.norm_x(x) * (self.high_note - self.low_note);
+        pitch + (self.scale.nearest(pitch) - pitch) * self.rounding
+    }
+
+    /// pitch of voice at time now, ending any glide that has completed
+    fn glide_pitch(&self, voice: &mut SurfaceVoice, now: Instant) -> f32 {
+        if let Some((from, start)) = voice.glide {
+            let elapsed = now.duration_since(start);
+            if elapsed >= self.glide {
+                voice.glide = None;
+            }
+            else {
+                let t = elapsed.as_micros() as f32 / self.glide.as_micros() as f32;
+                return from + (voice.target - from) * t;
+            }
+        }
+        voice.target
+    }
+
+    fn send(&self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>, msg: &str, mut args: Vec<OscType>) {
+        args.extend(self.args.iter().cloned());
+        let packet = OscPacket::Message(OscMessage {
+            addr: format!("{}/{}", self.address, msg),
+            args: Some(args),
+        });
+        transport.send((packet, None)).unwrap();
+    }
+}
+
+impl Controller for PitchSurface {
+    fn name(&self) -> &'static str {
+        "pitch_surface"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        let amplitude = num::clamp(contact.total_force / self.max_force, 0.0, 1.0);
+        // timbre increases as the finger moves up the surface
+        let timbre = 1.0 - self.region.norm_y(contact.y);
+
+        match contact.state {
+            contact::State::CONTACT_START => {
+                let channel = match self.voices.allocate(contact.id) {
+                    Some(channel) => channel,
+                    None => return Err("pitch surface has no free channels"),
+                };
+                let target = self.target(contact.x);
+                self.sounding.insert(contact.id, SurfaceVoice {
+                    channel: channel,
+                    target: target,
+                    pitch: target,
+                    glide: None,
+                });
+
+                let channel = OscType::Int(channel as i32);
+                self.send(transport, NOTE_ON, vec![channel.clone(), OscType::Float(target), OscType::Float(amplitude)]);
+                self.send(transport, TIMBRE, vec![channel, OscType::Float(timbre)]);
+                Ok(())
+            },
+            contact::State::CONTACT_MOVE => {
+                let target = self.target(contact.x);
+                if let Some(mut voice) = self.sounding.get(&contact.id).cloned() {
+                    if (target - voice.target).abs() >= GLIDE_THRESHOLD && self.glide > Duration::from_millis(0) {
+                        voice.glide = Some((voice.pitch, Instant::now()));
+                    }
+                    voice.target = target;
+                    voice.pitch = self.glide_pitch(&mut voice, Instant::now());
+                    self.sounding.insert(contact.id, voice);
+
+                    let channel = OscType::Int(voice.channel as i32);
+                    self.send(transport, PITCH, vec![channel.clone(), OscType::Float(voice.pitch)]);
+                    self.send(transport, TIMBRE, vec![channel.clone(), OscType::Float(timbre)]);
+                    self.send(transport, AMPLITUDE, vec![channel, OscType::Float(amplitude)]);
+                }
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                self.voices.release(contact.id);
+                if let Some(voice) = self.sounding.remove(&contact.id) {
+                    self.send(transport, NOTE_OFF, vec![OscType::Int(voice.channel as i32), OscType::Float(voice.pitch)]);
+                }
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    /// glides continue while a finger rests still, so are advanced here
+    fn tick(&mut self, 
+        now: Instant, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let gliding: Vec<u8> = self.sounding.iter()
+            .filter(|(_, voice)| voice.glide.is_some())
+            .map(|(id, _)| *id)
+            .collect();
+
+        for id in gliding {
+            let mut voice = self.sounding[&id];
+            voice.pitch = self.glide_pitch(&mut voice, now);
+            self.sounding.insert(id, voice);
+            self.send(transport, PITCH, vec![OscType::Int(voice.channel as i32), OscType::Float(voice.pitch)]);
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Gesture used to turn a knob
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KnobGesture {
+    /// drag up to increase
+    Vertical,
+    /// drag right to increase
+    Horizontal,
+    /// move clockwise around knob centre to increase
+    Circular,
+}
+
+impl KnobGesture {
+    pub fn new(gesture: Option<String>) -> Result<Self, &'static str> {
+        match gesture.as_ref().map(|g| g.as_str()) {
+            None | Some("vertical") => Ok(KnobGesture::Vertical),
+            Some("horizontal") => Ok(KnobGesture::Horizontal),
+            Some("circular") => Ok(KnobGesture::Circular),
+            _ => Err("unknown knob gesture, expected vertical, horizontal or circular"),
+        }
+    }
+}
+
+/// Knob controller
+///  A rotary control turned by dragging, either vertically, horizontally, or in a circle
+/// around the knob's centre. While a second finger rests on the knob, or when pressing
+/// lightly, changes are scaled down for fine adjustment. Double tapping resets the knob 
+/// to its initial value
+#[derive(Debug, Clone)]
+pub struct Knob {
+    /// OSC address
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// area of Morph covered by knob
+    region: Region,
+    gesture: KnobGesture,
+    /// minimum value for knob (default 0)
+    min: f32,
+    /// maximum value for knob (default 127)
+    max: f32,
+    /// increment for knob value, per unit of movement or, for circular, per turn over range
+    incr: f32,
+    /// value knob is reset to on double tap
+    initial: f32,
+    /// current value of knob
+    value: f32,
+    /// scale applied to changes when fine adjusting (default 0.1)
+    fine: f32,
+    /// contacts lighter than this fine adjust, if given
+    fine_force: Option<f32>,
+    /// maximum time between taps of a double tap (default 300ms)
+    double_tap: Duration,
+    /// start of last tap
+    last_tap: Option<Instant>,
+    /// contacts on knob, in order of arrival, the first turns the knob
+    contacts: Vec<u8>,
+    feedback: Feedback,
+    /// keeps turning after a flick, if enabled
+    inertia: Option<Inertia>,
+}
+
+impl Knob {
+    pub fn new(
+        address: String, args: Vec<ArgType>, region: Region, gesture: Option<String>,
+        min: Option<ArgType>, max: Option<ArgType>,
+        initial: Option<ArgType>, incr: Option<ArgType>,
+        fine: Option<f32>, fine_force: Option<f32>, double_tap_ms: Option<u64>,
+        feedback: Option<String>, inertia: Option<bool>, friction: Option<f32>) -> Result<Self, &'static str> {
+        let initial = initial.map_or(0.0, |x| f32::from(x));
+        let min = min.map_or(0.0, |x| f32::from(x));
+        let max = max.map_or(127.0, |x| f32::from(x));
+        Ok(Knob {
+            feedback: Feedback::new(feedback.unwrap_or_else(|| address.clone())),
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            region: region,
+            gesture: KnobGesture::new(gesture)?,
+            min: min,
+            max: max,
+            incr: incr.map_or(1.0, |x| f32::from(x)),
+            initial: initial,
+            value: initial,
+            fine: fine.unwrap_or(0.1),
+            fine_force: fine_force,
+            double_tap: Duration::from_millis(double_tap_ms.unwrap_or(300)),
+            last_tap: None,
+            contacts: vec![],
+            inertia: Inertia::new(inertia, friction, max - min),
+        })
+    }
+
+    /// angle of position around knob centre, increasing clockwise
+    fn angle(&self, x: f32, y: f32) -> f32 {
+        let cx = self.region.x + self.region.width * 0.5;
+        let cy = self.region.y + self.region.height * 0.5;
+        (y - cy).atan2(x - cx)
+    }
+
+    /// change in value for movement of contact from last position
+    fn movement(&self, contact: &contact::TrackedContact, last_x: f32, last_y: f32) -> f32 {
+        match self.gesture {
+            KnobGesture::Vertical => (last_y - contact.y) * self.incr,
+            KnobGesture::Horizontal => (contact.x - last_x) * self.incr,
+            KnobGesture::Circular => {
+                let pi = std::f32::consts::PI;
+                let mut turn = self.angle(contact.x, contact.y) - self.angle(last_x, last_y);
+                // unwrap, so crossing +/- pi is a small movement
+                if turn > pi {
+                    turn -= 2.0 * pi;
+                }
+                else if turn < -pi {
+                    turn += 2.0 * pi;
+                }
+                turn / (2.0 * pi) * (self.max - self.min) * self.incr
+            },
+        }
+    }
+
+    /// velocity, in value units per second, of contact turning knob
+    fn velocity(&self, contact: &contact::TrackedContact) -> f32 {
+        match self.gesture {
+            KnobGesture::Vertical => -contact.velocity_y * self.incr,
+            KnobGesture::Horizontal => contact.velocity_x * self.incr,
+            KnobGesture::Circular => {
+                let dx = contact.x - (self.region.x + self.region.width * 0.5);
+                let dy = contact.y - (self.region.y + self.region.height * 0.5);
+                let distance = dx * dx + dy * dy;
+                if distance == 0.0 {
+                    return 0.0;
+                }
+                // radians per second, clockwise
+                let turn = (dx * contact.velocity_y - dy * contact.velocity_x) / distance;
+                turn / (2.0 * std::f32::consts::PI) * (self.max - self.min) * self.incr
+            },
+        }
+    }
+
+    fn send(&mut self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        // build OSC argument list
+        let mut args = self.args.clone();
+        args.push(OscType::Float(self.value));
+        self.feedback.sent(&args);
+        // create OSC packet and send
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.address.clone(),
+            args: Some(args),
+        });
+        transport.send((packet, None)).unwrap();
+    }
+}
+
+impl Controller for Knob {
+    fn name(&self) -> &'static str {
+        "knob"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        self.feedback.contact(contact);
+
+        match contact.state {
+            contact::State::CONTACT_START => {
+                let now = Instant::now();
+                if self.contacts.len() == 0 {
+                    if self.last_tap.map_or(false, |t| now.duration_since(t) <= self.double_tap) {
+                        self.value = self.initial;
+                        self.last_tap = None;
+                        self.send(transport);
+                    }
+                    else {
+                        self.last_tap = Some(now);
+                    }
+                    if let Some(inertia) = self.inertia.as_mut() {
+                        inertia.touch();
+                    }
+                }
+                self.contacts.push(contact.id);
+                Ok(())
+            },
+            contact::State::CONTACT_MOVE => {
+                // only the first contact turns the knob
+                if self.contacts.first() != Some(&contact.id) {
+                    return Ok(());
+                }
+                let (last_x, last_y) = match contact.previous() {
+                    Some(last) => (last.x, last.y),
+                    None => return Ok(()),
+                };
+
+                let fine = self.contacts.len() > 1 || self.fine_force.map_or(false, |f| contact.total_force < f);
+                let scale = if fine { self.fine } else { 1.0 };
+                let movement = self.movement(contact, last_x, last_y) * scale;
+
+                // only send message if there was some movement
+                if movement != 0.0 {
+                    let value = num::clamp(self.value + movement, self.min, self.max);
+                    let velocity = self.velocity(contact) * scale;
+                    if let Some(inertia) = self.inertia.as_mut() {
+                        inertia.moved(velocity);
+                    }
+                    self.value = value;
+                    self.send(transport);
+                }
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                // once the turning contact lifts, the next in line takes over
+                self.contacts.retain(|id| *id != contact.id);
+                // a touch held for longer than a double tap is not a tap
+                let double_tap = self.double_tap;
+                self.last_tap = self.last_tap.filter(|t| t.elapsed() <= double_tap);
+                if self.contacts.is_empty() {
+                    if let Some(inertia) = self.inertia.as_mut() {
+                        inertia.release();
+                    }
+                }
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    /// keep moving after a flick
+    fn tick(&mut self, 
+        now: Instant, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let (value, min, max) = (self.value, self.min, self.max);
+        if let Some(value) = self.inertia.as_mut().and_then(|i| i.coast(now, value, min, max)) {
+            self.value = value;
+            self.send(transport);
+        }
+    }
+
+    /// value updates from host
+    fn osc_message(&mut self, 
+        msg: &OscMessage, 
+        _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        match self.feedback.receive(msg) {
+            Some(("", args)) => match osc_last_f32(args) {
+                Some(value) => {
+                    self.value = num::clamp(value, self.min, self.max);
+                    true
+                },
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn state(&self) -> Option<Value> {
+        Some(Value::from(self.value as f64))
+    }
+
+    fn set_state(&mut self, 
+        state: &Value, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        match state.as_f64() {
+            Some(value) => {
+                let value = num::clamp(value as f32, self.min, self.max);
+                if value != self.value {
+                    self.value = value;
+                    self.send(transport);
+                }
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Gain law of a crossfader
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FadeLaw {
+    Linear,
+    /// equal power, sin/cos, avoiding the dip in loudness at the centre
+    ConstantPower,
+    /// both sides at full gain, except when very close to either end
+    Cut,
+}
+
+impl FadeLaw {
+    pub fn new(law: Option<String>) -> Result<Self, &'static str> {
+        match law.as_ref().map(|l| l.as_str()) {
+            None | Some("linear") => Ok(FadeLaw::Linear),
+            Some("constant_power") => Ok(FadeLaw::ConstantPower),
+            Some("cut") => Ok(FadeLaw::Cut),
+            _ => Err("unknown fade law, expected linear, constant_power or cut"),
+        }
+    }
+
+    /// gains, 0..1, of A and B for position, 0 (all A) to 1 (all B)
+    pub fn gains(&self, position: f32) -> (f32, f32) {
+        // width of fade at either end for cut
+        const CUT: f32 = 0.05;
+
+        match *self {
+            FadeLaw::Linear => (1.0 - position, position),
+            FadeLaw::ConstantPower => {
+                let angle = position * std::f32::consts::FRAC_PI_2;
+                (angle.cos(), angle.sin())
+            },
+            FadeLaw::Cut => (
+                num::clamp((1.0 - position) / CUT, 0.0, 1.0), 
+                num::clamp(position / CUT, 0.0, 1.0)),
+        }
+    }
+}
+
+/// Crossfader controller
+///  Horizontal fader whose position sets complementary A and B gains, according to a
+/// fade law, sent as the last two message arguments
+#[derive(Debug, Clone)]
+pub struct Crossfader {
+    /// OSC address
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// area of Morph covered by crossfader
+    region: Region,
+    law: FadeLaw,
+    /// current position, 0 (all A) to 1 (all B)
+    position: f32,
+    feedback: Feedback,
+}
+
+impl Crossfader {
+    pub fn new(
+        address: String, args: Vec<ArgType>, region: Region, 
+        law: Option<String>, initial: Option<ArgType>, 
+        feedback: Option<String>) -> Result<Self, &'static str> {
+        Ok(Crossfader {
+            feedback: Feedback::new(feedback.unwrap_or_else(|| address.clone())),
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            region: region,
+            law: FadeLaw::new(law)?,
+            position: num::clamp(initial.map_or(0.5, |x| f32::from(x)), 0.0, 1.0),
+        })
+    }
+
+    fn send(&mut self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let (a, b) = self.law.gains(self.position);
+        let mut args = self.args.clone();
+        args.push(OscType::Float(a));
+        args.push(OscType::Float(b));
+        self.feedback.sent(&args);
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.address.clone(),
+            args: Some(args),
+        });
+        transport.send((packet, None)).unwrap();
+    }
+}
+
+impl Controller for Crossfader {
+    fn name(&self) -> &'static str {
+        "crossfader"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        self.feedback.contact(contact);
+
+        match contact.state {
+            contact::State::CONTACT_START | contact::State::CONTACT_MOVE => {
+                let position = self.region.norm_x(contact.x);
+                if position != self.position {
+                    self.position = position;
+                    self.send(transport);
+                }
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    /// position updates from host, as the last argument, 0 (all A) to 1 (all B)
+    fn osc_message(&mut self, 
+        msg: &OscMessage, 
+        _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        match self.feedback.receive(msg) {
+            Some(("", args)) => match osc_last_f32(args) {
+                Some(position) => {
+                    self.position = num::clamp(position, 0.0, 1.0);
+                    true
+                },
+                None => false,
+            },
+            _ => false,
+        }
+    }
+
+    fn state(&self) -> Option<Value> {
+        Some(Value::from(self.position as f64))
+    }
+
+    fn set_state(&mut self, 
+        state: &Value, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        match state.as_f64() {
+            Some(position) => {
+                let position = num::clamp(position as f32, 0.0, 1.0);
+                if position != self.position {
+                    self.position = position;
+                    self.send(transport);
+                }
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Fader bank controller
+///  A single region split into a number of faders, side by side when vertical (default)
+/// or stacked when horizontal. Each fader sends its value either to address/suffix, where
+/// suffix is the fader's name or index, or to address with the fader's index as the first
+/// argument
+#[derive(Debug, Clone)]
+pub struct FaderBank {
+    /// OSC address
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// area of Morph covered by bank
+    region: Region,
+    vertical: bool,
+    /// send index as an argument, rather than as an address suffix
+    indexed: bool,
+    /// address suffix of each fader
+    names: Vec<String>,
+    /// minimum value for faders (default 0)
+    min: f32,
+    /// maximum value for faders (default 127)
+    max: f32,
+    /// current value of each fader
+    values: Vec<f32>,
+    /// fader each contact started on
+    touches: HashMap<u8, usize>,
+    feedback: Feedback,
+}
+
+impl FaderBank {
+    pub fn new(
+        address: String, args: Vec<ArgType>, region: Region, 
+        count: Option<usize>, orientation: Option<String>, 
+        addressing: Option<String>, names: Option<Vec<String>>,
+        min: Option<ArgType>, max: Option<ArgType>, initial: Option<ArgType>,
+        feedback: Option<String>) -> Result<Self, &'static str> {
+        let count = match (count, names.as_ref()) {
+            (Some(count), Some(names)) if count != names.len() =>
+                return Err("fader bank count must match number of names"),
+            (_, Some(names)) => names.len(),
+            (count, None) => count.unwrap_or(8),
+        };
+        if count == 0 {
+            return Err("fader bank must have at least one fader");
+        }
+
+        let vertical = match orientation.as_ref().map(|o| o.as_str()) {
+            None | Some("vertical") => true,
+            Some("horizontal") => false,
+            _ => return Err("unknown fader orientation, expected vertical or horizontal"),
+        };
+
+        let indexed = match addressing.as_ref().map(|a| a.as_str()) {
+            None | Some("suffix") => false,
+            Some("index") => true,
+            _ => return Err("unknown fader addressing, expected suffix or index"),
+        };
+
+        let min = min.map_or(0.0, |x| f32::from(x));
+        Ok(FaderBank {
+            feedback: Feedback::new(feedback.unwrap_or_else(|| address.clone())),
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            region: region,
+            vertical: vertical,
+            indexed: indexed,
+            names: names.unwrap_or_else(|| (0..count).map(|i| i.to_string()).collect()),
+            min: min,
+            max: max.map_or(127.0, |x| f32::from(x)),
+            values: vec![initial.map_or(min, |x| f32::from(x)); count],
+            touches: HashMap::new(),
+        })
+    }
+
+    /// fader under contact
+    fn fader(&self, contact: &contact::TrackedContact) -> usize {
+        let across = if self.vertical { self.region.norm_x(contact.x) } else { self.region.norm_y(contact.y) };
+        cmp::min((across * self.values.len() as f32) as usize, self.values.len() - 1)
+    }
+
+    /// value of fader at contact's position
+    fn value(&self, contact: &contact::TrackedContact) -> f32 {
+        // values increase upwards, or to the right
+        let along = if self.vertical { 1.0 - self.region.norm_y(contact.y) } else { self.region.norm_x(contact.x) };
+        self.min + along * (self.max - self.min)
+    }
+
+    fn send(&mut self, fader: usize, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let (addr, mut args) = if self.indexed {
+            (self.address.clone(), vec![OscType::Int(fader as i32)])
+        }
+        else {
+            (format!("{}/{}", self.address, self.names[fader]), vec![])
+        };
+        args.extend(self.args.iter().cloned());
+        args.push(OscType::Float(self.values[fader]));
+        self.feedback.sent(&args);
+
+        let packet = OscPacket::Message(OscMessage {
+            addr: addr,
+            args: Some(args),
+        });
+        transport.send((packet, None)).unwrap();
+    }
+}
+
+impl Controller for FaderBank {
+    fn name(&self) -> &'static str {
+        "fader_bank"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        self.feedback.contact(contact);
+
+        match contact.state {
+            contact::State::CONTACT_START | contact::State::CONTACT_MOVE => {
+                // a contact stays with the fader it started on
+                let fader = match self.touches.get(&contact.id) {
+                    Some(fader) => *fader,
+                    None => {
+                        let fader = self.fader(contact);
+                        self.touches.insert(contact.id, fader);
+                        fader
+                    }
+                };
+
+                let value = self.value(contact);
+                if value != self.values[fader] {
+                    self.values[fader] = value;
+                    self.send(fader, transport);
+                }
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                self.touches.remove(&contact.id);
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    /// value updates from host, addressed to a fader in the same way as the bank sends
+    fn osc_message(&mut self, 
+        msg: &OscMessage, 
+        _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let (suffix, args) = match self.feedback.receive(msg) {
+            Some(update) => update,
+            None => return false,
+        };
+
+        let fader = if self.indexed {
+            if !suffix.is_empty() {
+                return false;
+            }
+            args.first().and_then(osc_f32).map(|i| i as usize)
+        }
+        else {
+            self.names.iter().position(|name| suffix.len() > 1 && name == &suffix[1..])
+        };
+
+        match (fader, osc_last_f32(args)) {
+            (Some(fader), Some(value)) if fader < self.values.len() => {
+                self.values[fader] = num::clamp(value, self.min, self.max);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    fn state(&self) -> Option<Value> {
+        Some(Value::Array(self.values.iter().map(|v| Value::from(*v as f64)).collect()))
+    }
+
+    fn set_state(&mut self, 
+        state: &Value, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let values = match state.as_array() {
+            Some(values) => values,
+            None => return false,
+        };
+
+        for (fader, value) in values.iter().enumerate().take(self.values.len()) {
+            if let Some(value) = value.as_f64() {
+                let value = num::clamp(value as f32, self.min, self.max);
+                if value != self.values[fader] {
+                    self.values[fader] = value;
+                    self.send(fader, transport);
+                }
+            }
+        }
+        true
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+const DAMP: &'static str = "damp";
+
+/// Strings controller
+///  Horizontal strings, spaced evenly down the region, that are plucked when a contact
+/// crosses them. Velocity comes from the speed of the crossing and the pressure of the 
+/// contact. A finger resting on, or moving slowly over, a string damps it
+#[derive(Debug, Clone)]
+pub struct Strings {
+    /// OSC address, plucks are sent to address, damping to address/damp
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// area of Morph covered by strings
+    region: Region,
+    /// note of each string, top to bottom
+    notes: Vec<u8>,
+    /// crossing speed, per second, that maps to full velocity (default 500)
+    max_speed: f32,
+    /// force that maps to full velocity (default 2000)
+    max_force: f32,
+    curve: VelocityCurve,
+    /// distance either side of a string within which a slow contact damps it (default 2)
+    damp_width: f32,
+    /// speed, per second, below which a contact damps (default 20)
+    damp_speed: f32,
+    /// string each contact is damping, if any
+    damping: HashMap<u8, Option<usize>>,
+    /// number of contacts damping each string
+    dampers: Vec<u32>,
+}
+
+impl Strings {
+    pub fn new(
+        address: String, args: Vec<ArgType>, region: Region, 
+        notes: Option<Vec<u8>>, max_speed: Option<f32>, max_force: Option<f32>, curve: Option<String>,
+        damp_width: Option<f32>, damp_speed: Option<f32>) -> Result<Self, &'static str> {
+        // default to guitar in standard tuning
+        let notes = notes.unwrap_or(vec![40, 45, 50, 55, 59, 64]);
+        if notes.len() == 0 {
+            return Err("strings must have at least one string");
+        }
+        let dampers = vec![0; notes.len()];
+
+        Ok(Strings {
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            region: region,
+            notes: notes,
+            max_speed: max_speed.unwrap_or(500.0),
+            max_force: max_force.unwrap_or(2000.0),
+            curve: VelocityCurve::new(curve)?,
+            damp_width: damp_width.unwrap_or(2.0),
+            damp_speed: damp_speed.unwrap_or(20.0),
+            damping: HashMap::new(),
+            dampers: dampers,
+        })
+    }
+
+    /// y position of string
+    fn string_y(&self, string: usize) -> f32 {
+        self.region.y + (string as f32 + 0.5) * self.region.height / self.notes.len() as f32
+    }
+
+    /// string within damping distance of y, if any
+    fn near(&self, y: f32) -> Option<usize> {
+        (0..self.notes.len()).find(|s| (self.string_y(*s) - y).abs() <= self.damp_width)
+    }
+
+    fn send(&self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>, addr: String, mut args: Vec<OscType>) {
+        args.extend(self.args.iter().cloned());
+        let packet = OscPacket::Message(OscMessage {
+            addr: addr,
+            args: Some(args),
+        });
+        info!("{:?}", packet);
+        transport.send((packet, None)).unwrap();
+    }
+
+    fn damp(&self, string: usize, on: bool, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        self.send(
+            transport, 
+            format!("{}/{}", self.address, DAMP), 
+            vec![OscType::Int(string as i32), OscType::Int(on as i32)]);
+    }
+
+    /// update damping of contact, a string is damped from the first contact resting
+    /// on it until the last leaves
+    fn update_damping(&mut self, id: u8, damping: Option<usize>, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let previous = self.damping.insert(id, damping).unwrap_or(None);
+        if previous != damping {
+            if let Some(string) = previous {
+                self.dampers[string] -= 1;
+                if self.dampers[string] == 0 {
+                    self.damp(string, false, transport);
+                }
+            }
+            if let Some(string) = damping {
+                self.dampers[string] += 1;
+                if self.dampers[string] == 1 {
+                    self.damp(string, true, transport);
+                }
+            }
+        }
+    }
+}
+
+impl Controller for Strings {
+    fn name(&self) -> &'static str {
+        "strings"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        match contact.state {
+            contact::State::CONTACT_START => {
+                let damping = self.near(contact.y);
+                self.update_damping(contact.id, damping, transport);
+                Ok(())
+            },
+            contact::State::CONTACT_MOVE => {
+                let current = match self.damping.get(&contact.id) {
+                    Some(current) => *current,
+                    None => return Ok(()),
+                };
+                let speed = contact.velocity_y.abs();
+
+                // pluck each string crossed since last frame
+                let (from, to) = (contact.previous().map_or(contact.y, |last| last.y), contact.y);
+                let pressure = num::clamp(contact.total_force / self.max_force, 0.0, 1.0);
+                let strength = 0.7 * num::clamp(speed / self.max_speed, 0.0, 1.0) + 0.3 * pressure;
+                for string in 0..self.notes.len() {
+                    let y = self.string_y(string);
+                    let crossed = (from < y && to >= y) || (from > y && to <= y);
+                    if crossed && current != Some(string) {
+                        self.send(
+                            transport, 
+                            self.address.clone(), 
+                            vec![
+                                OscType::Int(string as i32), 
+                                OscType::Int(self.notes[string] as i32), 
+                                OscType::Int(self.curve.velocity(strength))]);
+                    }
+                }
+
+                let damping = if speed < self.damp_speed { self.near(contact.y) } else { None };
+                self.update_damping(contact.id, damping, transport);
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                if self.damping.contains_key(&contact.id) {
+                    self.update_damping(contact.id, None, transport);
+                    self.damping.remove(&contact.id);
+                }
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Contact value that drives a macro target
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    Pressure,
+    /// horizontal position, left to right
+    X,
+    /// vertical position, bottom to top
+    Y,
+    /// size of contact
+    Area,
+    /// turn since touch start, centred at rest, clockwise increasing
+    Twist,
+    /// minor over major axis of contact
+    Flatness,
+}
+
+impl Source {
+    pub fn new(source: Option<String>) -> Result<Self, &'static str> {
+        match source.as_ref().map(|s| s.as_str()) {
+            None | Some("pressure") => Ok(Source::Pressure),
+            Some("x") => Ok(Source::X),
+            Some("y") => Ok(Source::Y),
+            Some("area") => Ok(Source::Area),
+            Some("twist") => Ok(Source::Twist),
+            Some("flatness") => Ok(Source::Flatness),
+            _ => Err("unknown macro source, expected pressure, x, y, area, twist or flatness"),
+        }
+    }
+
+    /// contact data source needs enabled
+    pub fn mask(&self) -> contact::Mask {
+        match self {
+            Source::Twist | Source::Flatness => contact::Mask::ELLIPSE,
+            _ => contact::Mask::empty(),
+        }
+    }
+}
+
+/// A single target of a macro, as given in the JSON IR
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MacroTarget {
+    pub address: String,
+    #[serde(default)]
+    pub args: Vec<ArgType>,
+    /// pressure (default), x, y, area, twist or flatness
+    pub source: Option<String>,
+    /// value sent for source at its lowest (default 0)
+    pub min: Option<f32>,
+    /// value sent for source at its highest (default 1), can be less than min to invert
+    pub max: Option<f32>,
+    /// argument template, the mapped value is $value (default ["$args", "$value"])
+    pub template: Option<Vec<TemplateArg>>,
+}
+
+#[derive(Debug, Clone)]
+struct Mapping {
+    address: String,
+    args: Vec<OscType>,
+    template: Template,
+    source: Source,
+    min: f32,
+    max: f32,
+}
+
+/// Macro controller
+///  A single touch drives a list of targets, each with its own address, arguments and
+/// mapping from a contact value, e.g. pressure opening a filter while turning volume down
+#[derive(Debug, Clone)]
+pub struct Macro {
+    /// area of Morph covered by macro
+    region: Region,
+    /// force that maps to full pressure (default 2000)
+    max_force: f32,
+    /// area that maps to full size (default 200)
+    max_area: f32,
+    /// degrees of twist, either way, that map to the ends of the range (default 90)
+    max_twist: f32,
+    targets: Vec<Mapping>,
+    twist: Twist,
+}
+
+impl Macro {
+    pub fn new(
+        region: Region, targets: Option<Vec<MacroTarget>>,
+        max_force: Option<f32>, max_area: Option<f32>, max_twist: Option<f32>) -> Result<Self, &'static str> {
+        let mut mappings = vec![];
+        for target in targets.unwrap_or(vec![]) {
+            mappings.push(Mapping {
+                address: target.address,
+                args: target.args.into_iter().map(|a| OscType::from(a)).collect(),
+                template: match target.template {
+                    Some(template) => Template::new(template)?,
+                    None => Template::from_placeholders(&["$args", "$value"]),
+                },
+                source: Source::new(target.source)?,
+                min: target.min.unwrap_or(0.0),
+                max: target.max.unwrap_or(1.0),
+            });
+        }
+
+        if mappings.len() == 0 {
+            return Err("macro must have at least one target");
+        }
+
+        Ok(Macro {
+            region: region,
+            max_force: max_force.unwrap_or(2000.0),
+            max_area: max_area.unwrap_or(200.0),
+            max_twist: max_twist.unwrap_or(90.0),
+            targets: mappings,
+            twist: Twist::new(),
+        })
+    }
+
+    /// send each target its value, for normalised source values
+    fn send(
+        &mut self, 
+        contact: &contact::TrackedContact, pressure: f32, x: f32, y: f32, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let area = num::clamp(contact.area / self.max_area, 0.0, 1.0);
+        let twist = num::clamp(0.5 + self.twist.update(contact) / (2.0 * self.max_twist), 0.0, 1.0);
+        let flatness = expression::flatness(contact);
+        for target in self.targets.iter_mut() {
+            let v = match target.source {
+                Source::Pressure => pressure,
+                Source::X => x,
+                Source::Y => y,
+                Source::Area => area,
+                Source::Twist => twist,
+                Source::Flatness => flatness,
+            };
+            let value = target.min + v * (target.max - target.min);
+            let packet = OscPacket::Message(OscMessage {
+                addr: target.address.clone(),
+                args: Some(target.template.build(contact, &self.region, &target.args, value)),
+            });
+            transport.send((packet, None)).unwrap();
+        }
+    }
+}
+
+impl Controller for Macro {
+    fn name(&self) -> &'static str {
+        "macro"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        let x = self.region.norm_x(contact.x);
+        let y = 1.0 - self.region.norm_y(contact.y);
+        match contact.state {
+            contact::State::CONTACT_START | contact::State::CONTACT_MOVE => {
+                let pressure = num::clamp(contact.total_force / self.max_force, 0.0, 1.0);
+                self.send(contact, pressure, x, y, transport);
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                // pressure is released, positions stay where they were left
+                self.send(contact, 0.0, x, y, transport);
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn contact_mask(&self) -> contact::Mask {
+        self.targets.iter().fold(contact::Mask::empty(), |mask, target|
+            mask | target.source.mask() | target.template.mask())
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Chord controller
+///  A pad that sends a note event for each note of a chord, note on with a velocity
+/// from the force of the touch and note off, velocity 0, on release
+#[derive(Debug, Clone)]
+pub struct Chord {
+    /// OSC address
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    notes: Vec<u8>,
+    /// force that maps to full velocity (default 2000)
+    max_force: f32,
+    curve: VelocityCurve,
+    /// contacts holding chord, it is released when the last lifts
+    contacts: Vec<u8>,
+}
+
+impl Chord {
+    pub fn new(
+        address: String, args: Vec<ArgType>, notes: Option<Vec<u8>>,
+        max_force: Option<f32>, curve: Option<String>) -> Result<Self, &'static str> {
+        let notes = notes.unwrap_or(vec![]);
+        if notes.len() == 0 {
+            return Err("chord must have at least one note");
+        }
+
+        Ok(Chord {
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            notes: notes,
+            max_force: max_force.unwrap_or(2000.0),
+            curve: VelocityCurve::new(curve)?,
+            contacts: vec![],
+        })
+    }
+
+    fn send(&self, velocity: i32, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        for note in &self.notes {
+            let mut args = vec![OscType::Int(*note as i32), OscType::Int(velocity)];
+            args.extend(self.args.iter().cloned());
+            let packet = OscPacket::Message(OscMessage {
+                addr: self.address.clone(),
+                args: Some(args),
+            });
+            info!("{:?}", packet);
+            transport.send((packet, None)).unwrap();
+        }
+    }
+}
+
+impl Controller for Chord {
+    fn name(&self) -> &'static str {
+        "chord"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        match contact.state {
+            contact::State::CONTACT_START => {
+                if self.contacts.len() == 0 {
+                    self.send(self.curve.velocity(contact.total_force / self.max_force), transport);
+                }
+                self.contacts.push(contact.id);
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                let held = self.contacts.len();
+                self.contacts.retain(|id| *id != contact.id);
+                if held > 0 && self.contacts.len() == 0 {
+                    self.send(0, transport);
+                }
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Shape of a joystick's movement range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoystickGate {
+    /// deflection limited to radius in every direction
+    Circle,
+    /// each axis limited to radius independently, so corners reach full deflection on both
+    Square,
+}
+
+/// How a released joystick returns to centre over its return time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReturnCurve {
+    Linear,
+    /// fast at first, slowing towards centre
+    EaseOut,
+    /// slow at first, speeding up towards centre
+    EaseIn,
+    /// overshoots centre, settling with a damped oscillation
+    Spring,
+}
+
+impl ReturnCurve {
+    pub fn new(curve: Option<String>) -> Result<Self, &'static str> {
+        match curve.as_ref().map(|c| c.as_str()) {
+            None | Some("linear") => Ok(ReturnCurve::Linear),
+            Some("ease_out") => Ok(ReturnCurve::EaseOut),
+            Some("ease_in") => Ok(ReturnCurve::EaseIn),
+            Some("spring") => Ok(ReturnCurve::Spring),
+            _ => Err("unknown return curve, expected linear, ease_out, ease_in or spring"),
+        }
+    }
+
+    /// fraction of deflection remaining at t, 0..1, through return
+    pub fn remaining(&self, t: f32) -> f32 {
+        let t = num::clamp(t, 0.0, 1.0);
+        match *self {
+            ReturnCurve::Linear => 1.0 - t,
+            ReturnCurve::EaseOut => (1.0 - t) * (1.0 - t),
+            ReturnCurve::EaseIn => 1.0 - t * t,
+            ReturnCurve::Spring => (-5.0 * t).exp() * (3.0 * std::f32::consts::PI * t).cos() * (1.0 - t),
+        }
+    }
+}
+
+/// Joystick controller
+///  Reports deflection, -1..1 on each axis with y increasing upwards, from either the
+/// point first touched (relative) or the centre of its region (absolute), limited to 
+/// radius by a circular or square gate. On release it springs back to centre over its 
+/// return time, sending values along the way
+#[derive(Debug, Clone)]
+pub struct Joystick {
+    /// OSC address
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// area of Morph covered by joystick
+    region: Region,
+    /// deflection measured from initial touch, rather than region centre
+    relative: bool,
+    /// distance for full deflection (default half the region's smaller side)
+    radius: f32,
+    gate: JoystickGate,
+    /// time to return to centre once released (default 200ms)
+    return_time: Duration,
+    curve: ReturnCurve,
+    /// contact moving joystick
+    contact: Option<u8>,
+    /// point deflection is measured from
+    origin: (f32, f32),
+    /// current deflection
+    position: (f32, f32),
+    /// start of return, and position returning from
+    returning: Option<(Instant, (f32, f32))>,
+}
+
+impl Joystick {
+    pub fn new(
+        address: String, args: Vec<ArgType>, region: Region,
+        mode: Option<String>, radius: Option<f32>, gate: Option<String>,
+        return_ms: Option<u64>, return_curve: Option<String>) -> Result<Self, &'static str> {
+        let relative = match mode.as_ref().map(|m| m.as_str()) {
+            None | Some("absolute") => false,
+            Some("relative") => true,
+            _ => return Err("unknown joystick mode, expected absolute or relative"),
+        };
+
+        let gate = match gate.as_ref().map(|g| g.as_str()) {
+            None | Some("circle") => JoystickGate::Circle,
+            Some("square") => JoystickGate::Square,
+            _ => return Err("unknown joystick gate, expected circle or square"),
+        };
+
+        let radius = radius.unwrap_or(region.width.min(region.height) * 0.5);
+        if radius <= 0.0 {
+            return Err("joystick radius must be greater than 0");
+        }
+
+        Ok(Joystick {
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            region: region,
+            relative: relative,
+            radius: radius,
+            gate: gate,
+            return_time: Duration::from_millis(return_ms.unwrap_or(200)),
+            curve: ReturnCurve::new(return_curve)?,
+            contact: None,
+            origin: (0.0, 0.0),
+            position: (0.0, 0.0),
+            returning: None,
+        })
+    }
+
+    /// deflection of contact from origin, within gate
+    fn deflection(&self, contact: &contact::TrackedContact) -> (f32, f32) {
+        let x = (contact.x - self.origin.0) / self.radius;
+        let y = (self.origin.1 - contact.y) / self.radius;
+        match self.gate {
+            JoystickGate::Circle => {
+                let length = (x * x + y * y).sqrt();
+                if length > 1.0 { (x / length, y / length) } else { (x, y) }
+            },
+            JoystickGate::Square => (num::clamp(x, -1.0, 1.0), num::clamp(y, -1.0, 1.0)),
+        }
+    }
+
+    fn update(&mut self, position: (f32, f32), transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        if position == self.position {
+            return;
+        }
+        self.position = position;
+
+        let mut args = self.args.clone();
+        args.push(OscType::Float(position.0));
+        args.push(OscType::Float(position.1));
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.address.clone(),
+            args: Some(args),
+        });
+        transport.send((packet, None)).unwrap();
+    }
+}
+
+impl Controller for Joystick {
+    fn name(&self) -> &'static str {
+        "joystick"
+    }
+
+    fn touch_start(&mut self, 
+             contact: &contact::TrackedContact, 
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        match contact.state {
+            contact::State::CONTACT_START => {
+                // only one contact moves the stick
+                if self.contact.is_some() {
+                    return Ok(());
+                }
+                self.contact = Some(contact.id);
+                self.returning = None;
+
+                self.origin = if self.relative {
+                    // grabbing a returning stick continues from where it is
+                    (contact.x - self.position.0 * self.radius, contact.y + self.position.1 * self.radius)
+                }
+                else {
+                    (self.region.x + self.region.width * 0.5, self.region.y + self.region.height * 0.5)
+                };
+                let position = self.deflection(contact);
+                self.update(position, transport);
+                Ok(())
+            },
+            contact::State::CONTACT_MOVE => {
+                if self.contact == Some(contact.id) {
+                    let position = self.deflection(contact);
+                    self.update(position, transport);
+                }
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                if self.contact == Some(contact.id) {
+                    self.contact = None;
+                    if self.return_time == Duration::from_millis(0) {
+                        self.update((0.0, 0.0), transport);
+                    }
+                    else {
+                        self.returning = Some((Instant::now(), self.position));
+                    }
+                }
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self, 
+        contact: &contact::TrackedContact, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    /// spring back to centre once released
+    fn tick(&mut self, 
+        now: Instant, 
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        if let Some((start, from)) = self.returning {
+            let elapsed = if now > start { now.duration_since(start) } else { Duration::from_millis(0) };
+            if elapsed >= self.return_time {
+                self.returning = None;
+                self.update((0.0, 0.0), transport);
+            }
+            else {
+                let t = elapsed.as_secs_f32() / self.return_time.as_secs_f32();
+                let remaining = self.curve.remaining(t);
+                self.update((from.0 * remaining, from.1 * remaining), transport);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn contact(id: u8, state: contact::State, x: f32, y: f32) -> contact::TrackedContact {
+        contact::TrackedContact::from(contact::Contact::at(id, state, x, y))
+    }
+
+    fn phase(packet: &OscPacket) -> Option<i32> {
+        match packet {
+            OscPacket::Message(OscMessage { args: Some(args), .. }) => match args.first() {
+                Some(OscType::Int(phase)) => Some(*phase),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn keyboard_channels_fit_lower_zone() {
+        let region = Region { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+        let keyboard = |channels| Keyboard::new(
+            "/keyboard".to_string(), vec![], region,
+            None, None, None, None, None, channels, None, None);
+        assert!(keyboard(None).is_ok());
+        assert!(keyboard(Some(15)).is_ok());
+        assert!(keyboard(Some(0)).is_err());
+        assert!(keyboard(Some(16)).is_err());
+    }
+
+    #[test]
+    fn drums_sending_same_messages_choke_each_other() {
+        let chokes = ChokeGroups::default();
+        let drum = || Drum::new(
+            "/drum".to_string(), vec![], Some(1), None, None, Some(1), chokes.clone()).unwrap();
+        let (mut first, mut second) = (drum(), drum());
+        let (sender, receiver) = channel();
+
+        first.touch_start(&contact(1, contact::State::CONTACT_START, 10.0, 10.0), &sender).unwrap();
+        second.touch_start(&contact(2, contact::State::CONTACT_START, 50.0, 10.0), &sender).unwrap();
+        // striking the same drum again does not choke it
+        second.touch_start(&contact(3, contact::State::CONTACT_START, 50.0, 10.0), &sender).unwrap();
+
+        let chokes = receiver.try_iter().filter(|(packet, _)| phase(packet) == Some(DRUM_CHOKE)).count();
+        assert_eq!(chokes, 1);
+    }
+
+    #[test]
+    fn choke_sent_where_choked_drum_sent_its_strike() {
+        let chokes = ChokeGroups::default();
+        let drum = |address: &str| Drum::new(
+            address.to_string(), vec![], Some(1), None, None, Some(1), chokes.clone()).unwrap();
+        let (mut hats, mut open_hats) = (drum("/hats"), drum("/open_hats"));
+        let (first, first_receiver) = channel();
+        let (second, second_receiver) = channel();
+
+        open_hats.touch_start(&contact(1, contact::State::CONTACT_START, 10.0, 10.0), &first).unwrap();
+        hats.touch_start(&contact(2, contact::State::CONTACT_START, 50.0, 10.0), &second).unwrap();
+
+        let choked: Vec<_> = first_receiver.try_iter()
+            .filter(|(packet, _)| phase(packet) == Some(DRUM_CHOKE))
+            .filter_map(|(packet, _)| match packet {
+                OscPacket::Message(msg) => Some(msg.addr),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(choked, vec!["/open_hats".to_string()]);
+        assert!(second_receiver.try_iter().all(|(packet, _)| phase(&packet) != Some(DRUM_CHOKE)));
+    }
+
+    #[test]
+    fn sequencer_steps_stay_on_grid() {
+        let region = Region { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+        // 125ms steps
+        let mut sequencer = Sequencer::new(
+            "/seq".to_string(), vec![], region, None, None, Some(120.0), Some(4), None);
+        let (sender, receiver) = channel();
+        let start = Instant::now();
+
+        // late ticks, then a stall of several steps
+        for &ms in [0, 130, 260, 380, 499, 900, 999].iter() {
+            sequencer.tick(start + Duration::from_millis(ms), &sender);
+        }
+
+        let playheads: Vec<_> = receiver.try_iter().filter_map(|(packet, _)| match packet {
+            OscPacket::Message(OscMessage { addr, args: Some(args) }) if addr == "/seq/playhead" => args.first().cloned(),
+            _ => None,
+        }).collect();
+        let expected: Vec<_> = [0, 1, 2, 3, 7].iter().map(|&p| OscType::Int(p)).collect();
+        assert_eq!(playheads, expected);
+        assert_eq!(sequencer.next_step, Some(start + Duration::from_millis(1000)));
+    }
+
+    #[test]
+    fn pitch_surface_channels_fit_lower_zone() {
+        let region = Region { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+        let surface = |channels| PitchSurface::new(
+            "/surface".to_string(), vec![], region,
+            None, None, None, None, None, None, channels, None);
+        assert!(surface(None).is_ok());
+        assert!(surface(Some(0)).is_err());
+        assert!(surface(Some(16)).is_err());
+    }
+
+    #[test]
+    fn fader_bank_count_must_match_names() {
+        let region = Region { x: 0.0, y: 0.0, width: 40.0, height: 40.0 };
+        let bank = |count, names: Option<Vec<&str>>| FaderBank::new(
+            "/fader".to_string(), vec![], region, count, None, None,
+            names.map(|n| n.iter().map(|n| n.to_string()).collect()), None, None, None, None);
+
+        assert_eq!(bank(None, None).unwrap().values.len(), 8);
+        assert_eq!(bank(None, Some(vec!["a", "b"])).unwrap().values.len(), 2);
+        assert_eq!(bank(Some(2), Some(vec!["a", "b"])).unwrap().values.len(), 2);
+        assert!(bank(Some(3), Some(vec!["a", "b"])).is_err());
+    }
+
+    #[test]
+    fn string_damped_until_last_finger_lifts() {
+        let region = Region { x: 0.0, y: 0.0, width: 100.0, height: 60.0 };
+        let mut strings = Strings::new(
+            "/strings".to_string(), vec![], region, None, None, None, None, None, None).unwrap();
+        let (sender, receiver) = channel();
+
+        // both fingers rest on the top string
+        strings.touch_start(&contact(1, contact::State::CONTACT_START, 10.0, 5.0), &sender).unwrap();
+        strings.touch_start(&contact(2, contact::State::CONTACT_START, 50.0, 5.0), &sender).unwrap();
+        strings.touch_end(&contact(1, contact::State::CONTACT_END, 10.0, 5.0), &sender);
+        let damps: Vec<_> = receiver.try_iter().map(|(packet, _)| packet).collect();
+        assert_eq!(damps.len(), 1);
+
+        strings.touch_end(&contact(2, contact::State::CONTACT_END, 50.0, 5.0), &sender);
+        match receiver.try_recv() {
+            Ok((OscPacket::Message(msg), _)) => {
+                assert_eq!(msg.addr, "/strings/damp");
+                assert_eq!(msg.args, Some(vec![OscType::Int(0), OscType::Int(0)]));
+            },
+            _ => panic!("expected damp off"),
+        }
+    }
+
+    #[test]
+    fn inertia_coasts_to_a_stop() {
+        assert!(Inertia::new(Some(true), None, 0.0).is_none());
+
+        let mut inertia = Inertia::new(Some(true), Some(0.9), 100.0).unwrap();
+        inertia.touch();
+        inertia.moved(50.0);
+        inertia.release();
+        let start = Instant::now();
+        let stopped = (1..100).map(|s| start + Duration::from_millis(100 * s))
+            .find(|&now| inertia.coast(now, 50.0, 0.0, 100.0).is_none());
+        assert!(stopped.is_some());
+    }
+
+    #[test]
+    fn vslider_moves_from_previous_frame() {
+        let mut slider = VSlider::new(
+            "/slider".to_string(), vec![], None, None, Some(ArgType::FType(50.0)), None, None, None, None);
+        let mut tracker = contact::ContactTracker::new();
+        let (sender, receiver) = channel();
+        let start = Instant::now();
+
+        slider.touch_start(tracker.update(contact::Contact::at(1, contact::State::CONTACT_START, 10.0, 40.0), start), &sender).unwrap();
+        slider.touch_move(tracker.update(contact::Contact::at(1, contact::State::CONTACT_MOVE, 10.0, 30.0), start), &sender);
+        slider.touch_move(tracker.update(contact::Contact::at(1, contact::State::CONTACT_MOVE, 10.0, 35.0), start), &sender);
+
+        let values: Vec<_> = receiver.try_iter().filter_map(|(packet, _)| match packet {
+            OscPacket::Message(OscMessage { args: Some(args), .. }) => args.last().cloned(),
+            _ => None,
+        }).collect();
+        assert_eq!(values, vec![OscType::Float(60.0), OscType::Float(55.0)]);
+    }
+}
@@ -0,0 +1,369 @@
+//! Description:
+//!    Envelope controller, a curve of breakpoints that are grabbed and
+//!    dragged, either an ADSR or an arbitrary breakpoint curve
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::net::{SocketAddrV4};
+use std::sync::mpsc::{Sender};
+use std::collections::HashMap;
+
+use rosc::{OscPacket, OscMessage, OscType};
+
+use serde_json::Value;
+
+use crate::sensel::*;
+
+use super::controllers::*;
+use super::registry::BuildContext;
+
+const POINT: &'static str = "point";
+
+/// IR fields of an envelope controller
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EnvelopeConfig {
+    pub address: String,
+    pub args: Vec<ArgType>,
+    /// adsr or breakpoints (default)
+    pub shape: Option<String>,
+    /// initial points, [x, y] each 0..1, with y increasing upwards
+    pub points: Option<Vec<(f32, f32)>>,
+    /// distance, relative to the region, within which a point is grabbed (default 0.1)
+    pub grab_radius: Option<f32>,
+    /// curve (default), sending every point on change, or point, sending just the point moved
+    pub output: Option<String>,
+}
+
+/// build envelope controller from its JSON IR
+pub fn factory(json: &Value, context: &BuildContext) -> Result<Box<Controller>, &'static str> {
+    let config: EnvelopeConfig = serde_json::from_value(json.clone())
+        .map_err(|_| "unexpected format error with envelope controller")?;
+    Ok(Box::new(
+        Envelope::new(
+            config.address, config.args, context.region,
+            config.shape, config.points, config.grab_radius, config.output)?))
+}
+
+/// A point of the curve, along with the constraints on its movement
+#[derive(Debug, Clone, Copy)]
+struct Breakpoint {
+    x: f32,
+    y: f32,
+    lock_x: bool,
+    lock_y: bool,
+    /// point whose level follows this one's, e.g. the end of an ADSR's sustain
+    link_y: Option<usize>,
+}
+
+impl Breakpoint {
+    fn new(x: f32, y: f32, lock_x: bool, lock_y: bool) -> Self {
+        Breakpoint {
+            x: num::clamp(x, 0.0, 1.0),
+            y: num::clamp(y, 0.0, 1.0),
+            lock_x: lock_x,
+            lock_y: lock_y,
+            link_y: None,
+        }
+    }
+}
+
+/// Envelope controller
+///  Displays a curve of breakpoints across its region, with time increasing to the right
+/// and level upwards. Touching near a point grabs it and dragging moves it, keeping points
+/// in time order, with each contact holding its own point. For an ADSR the first and last
+/// points stay at zero level, the attack at full level, and the release starts from the
+/// sustain level; for arbitrary breakpoints the first and last points stay at either end
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    /// OSC address
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// area of Morph covered by envelope
+    region: Region,
+    points: Vec<Breakpoint>,
+    grab_radius: f32,
+    /// send only the point moved, rather than the whole curve
+    point_output: bool,
+    /// point grabbed by each contact
+    grabs: HashMap<u8, usize>,
+}
+
+impl Envelope {
+    pub fn new(
+        address: String, args: Vec<ArgType>, region: Region,
+        shape: Option<String>, points: Option<Vec<(f32, f32)>>,
+        grab_radius: Option<f32>, output: Option<String>) -> Result<Self, &'static str> {
+        let points = match shape.as_ref().map(|s| s.as_str()) {
+            Some("adsr") => {
+                let p = points.unwrap_or(vec![(0.0, 0.0), (0.1, 1.0), (0.3, 0.6), (0.7, 0.6), (1.0, 0.0)]);
+                if p.len() != 5 {
+                    return Err("adsr envelope must have 5 points");
+                }
+                let mut sustain = Breakpoint::new(p[2].0, p[2].1, false, false);
+                sustain.link_y = Some(3);
+                vec![
+                    Breakpoint::new(0.0, 0.0, true, true),
+                    Breakpoint::new(p[1].0, 1.0, false, true),
+                    sustain,
+                    Breakpoint::new(p[3].0, p[2].1, false, true),
+                    Breakpoint::new(p[4].0, 0.0, false, true),
+                ]
+            },
+            None | Some("breakpoints") => {
+                let p = points.unwrap_or(vec![(0.0, 0.0), (0.5, 1.0), (1.0, 0.0)]);
+                if p.len() < 2 {
+                    return Err("breakpoint envelope must have at least 2 points");
+                }
+                let last = p.len() - 1;
+                p.iter().enumerate().map(|(i, &(x, y))| {
+                    match i {
+                        0 => Breakpoint::new(0.0, y, true, false),
+                        i if i == last => Breakpoint::new(1.0, y, true, false),
+                        _ => Breakpoint::new(x, y, false, false),
+                    }
+                }).collect()
+            },
+            _ => return Err("unknown envelope shape, expected adsr or breakpoints"),
+        };
+
+        if points.windows(2).any(|w| w[0].x > w[1].x) {
+            return Err("envelope points must be in time order");
+        }
+
+        let point_output = match output.as_ref().map(|o| o.as_str()) {
+            None | Some("curve") => false,
+            Some("point") => true,
+            _ => return Err("unknown envelope output, expected curve or point"),
+        };
+
+        Ok(Envelope {
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            region: region,
+            points: points,
+            grab_radius: grab_radius.unwrap_or(0.1),
+            point_output: point_output,
+            grabs: HashMap::new(),
+        })
+    }
+
+    /// contact position, relative to region, with level increasing upwards
+    fn position(&self, contact: &contact::TrackedContact) -> (f32, f32) {
+        (self.region.norm_x(contact.x), 1.0 - self.region.norm_y(contact.y))
+    }
+
+    /// nearest point to position, within grab radius, not already grabbed
+    fn nearest(&self, (x, y): (f32, f32)) -> Option<usize> {
+        self.points.iter().enumerate()
+            .filter(|(i, _)| !self.grabs.values().any(|g| g == i))
+            .map(|(i, p)| (i, ((p.x - x).powi(2) + (p.y - y).powi(2)).sqrt()))
+            .filter(|(_, d)| *d <= self.grab_radius)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(i, _)| i)
+    }
+
+    /// move point towards position, within its constraints, returns true if it moved
+    fn move_point(&mut self, index: usize, (x, y): (f32, f32)) -> bool {
+        // time is kept between neighbouring points
+        let before = if index > 0 { self.points[index - 1].x } else { 0.0 };
+        let after = if index + 1 < self.points.len() { self.points[index + 1].x } else { 1.0 };
+
+        let point = self.points[index];
+        let new_x = if point.lock_x { point.x } else { num::clamp(x, before, after) };
+        let new_y = if point.lock_y { point.y } else { num::clamp(y, 0.0, 1.0) };
+        if new_x == point.x && new_y == point.y {
+            return false;
+        }
+
+        self.points[index].x = new_x;
+        self.points[index].y = new_y;
+        if let Some(link) = point.link_y {
+            self.points[link].y = new_y;
+        }
+        true
+    }
+
+    fn send(&self, index: usize, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let mut args = self.args.clone();
+        let addr = if self.point_output {
+            let point = self.points[index];
+            args.push(OscType::Int(index as i32));
+            args.push(OscType::Float(point.x));
+            args.push(OscType::Float(point.y));
+            format!("{}/{}", self.address, POINT)
+        }
+        else {
+            for point in &self.points {
+                args.push(OscType::Float(point.x));
+                args.push(OscType::Float(point.y));
+            }
+            self.address.clone()
+        };
+
+        let packet = OscPacket::Message(OscMessage {
+            addr: addr,
+            args: Some(args),
+        });
+        transport.send((packet, None)).unwrap();
+    }
+}
+
+impl Controller for Envelope {
+    fn name(&self) -> &'static str {
+        "envelope"
+    }
+
+    fn touch_start(&mut self,
+             contact: &contact::TrackedContact,
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        match contact.state {
+            contact::State::CONTACT_START => {
+                // touches away from any point are ignored
+                if let Some(index) = self.nearest(self.position(contact)) {
+                    self.grabs.insert(contact.id, index);
+                }
+                Ok(())
+            },
+            contact::State::CONTACT_MOVE => {
+                if let Some(index) = self.grabs.get(&contact.id).cloned() {
+                    let position = self.position(contact);
+                    if self.move_point(index, position) {
+                        self.send(index, transport);
+                    }
+                }
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                self.grabs.remove(&contact.id);
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    /// points, as [x, y] pairs
+    fn state(&self) -> Option<Value> {
+        Some(Value::Array(self.points.iter().map(|p|
+            Value::Array(vec![Value::from(p.x as f64), Value::from(p.y as f64)])).collect()))
+    }
+
+    fn set_state(&mut self,
+        state: &Value,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let points: Vec<(f32, f32)> = match serde_json::from_value(state.clone()) {
+            Ok(points) => points,
+            Err(_) => return false,
+        };
+        if points.len() != self.points.len() {
+            return false;
+        }
+
+        // points are assigned together, rather than dragged one at a time, as each
+        // would be held between its neighbours' old times
+        let mut restored = self.points.clone();
+        for (point, &(x, y)) in restored.iter_mut().zip(points.iter()) {
+            if !point.lock_x {
+                point.x = num::clamp(x, 0.0, 1.0);
+            }
+            if !point.lock_y {
+                point.y = num::clamp(y, 0.0, 1.0);
+            }
+        }
+        for index in 0..restored.len() {
+            if let Some(link) = restored[index].link_y {
+                restored[link].y = restored[index].y;
+            }
+        }
+        if restored.windows(2).any(|w| w[0].x > w[1].x) {
+            return false;
+        }
+
+        let changed: Vec<usize> = (0..restored.len())
+            .filter(|&i| restored[i].x != self.points[i].x || restored[i].y != self.points[i].y)
+            .collect();
+        self.points = restored;
+
+        if self.point_output {
+            for index in changed {
+                self.send(index, transport);
+            }
+        }
+        else if let Some(&index) = changed.first() {
+            self.send(index, transport);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn region() -> Region {
+        Region { x: 0.0, y: 0.0, width: 100.0, height: 100.0 }
+    }
+
+    fn points(envelope: &Envelope) -> Vec<(f32, f32)> {
+        envelope.points.iter().map(|p| (p.x, p.y)).collect()
+    }
+
+    #[test]
+    fn restore_moves_points_past_old_neighbours() {
+        let mut envelope = Envelope::new(
+            "/env".to_string(), vec![], region(), None,
+            Some(vec![(0.0, 0.0), (0.2, 1.0), (0.4, 0.5), (1.0, 0.0)]), None, None).unwrap();
+        let (sender, receiver) = channel();
+
+        let state = serde_json::json!([[0.0, 0.0], [0.6, 1.0], [0.8, 0.5], [1.0, 0.0]]);
+        assert!(envelope.set_state(&state, &sender));
+        assert_eq!(points(&envelope), vec![(0.0, 0.0), (0.6, 1.0), (0.8, 0.5), (1.0, 0.0)]);
+        // whole curve is sent once
+        assert_eq!(receiver.try_iter().count(), 1);
+    }
+
+    #[test]
+    fn restore_rejects_points_out_of_time_order() {
+        let mut envelope = Envelope::new(
+            "/env".to_string(), vec![], region(), None,
+            Some(vec![(0.0, 0.0), (0.3, 1.0), (0.6, 0.5), (1.0, 0.0)]), None, None).unwrap();
+        let (sender, receiver) = channel();
+
+        let before = points(&envelope);
+        let state = serde_json::json!([[0.0, 0.0], [0.7, 1.0], [0.5, 0.5], [1.0, 0.0]]);
+        assert!(!envelope.set_state(&state, &sender));
+        assert_eq!(points(&envelope), before);
+        assert_eq!(receiver.try_iter().count(), 0);
+    }
+
+    #[test]
+    fn restore_honours_adsr_constraints() {
+        let mut envelope = Envelope::new(
+            "/env".to_string(), vec![], region(), Some("adsr".to_string()), None, None, None).unwrap();
+        let (sender, _receiver) = channel();
+
+        let state = serde_json::json!([[0.3, 0.5], [0.2, 0.4], [0.5, 0.3], [0.8, 0.9], [0.9, 0.7]]);
+        assert!(envelope.set_state(&state, &sender));
+        // start stays fixed, attack at full level, release starts from sustain level
+        assert_eq!(points(&envelope), vec![(0.0, 0.0), (0.2, 1.0), (0.5, 0.3), (0.8, 0.3), (0.9, 0.0)]);
+    }
+}
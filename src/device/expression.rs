@@ -0,0 +1,80 @@
+//! Description:
+//!    Expression derived from the shape of a contact, i.e. twist, the change
+//!    in orientation of its ellipse since touch start, and flatness, how
+//!    elongated it is, e.g. a finger laid flat rather than a fingertip
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::collections::HashMap;
+
+use crate::sensel::*;
+
+/// ellipse orientation, in degrees, wraps every half turn
+const HALF_TURN: f32 = 180.0;
+
+/// flatness of contact, minor over major axis of its ellipse, 1 for a circle and
+/// approaching 0 as it elongates, 1 if ellipse is not enabled
+pub fn flatness(contact: &contact::Contact) -> f32 {
+    match contact.ellipse {
+        Some(e) if e.major_axis > 0.0 => num::clamp(e.minor_axis / e.major_axis, 0.0, 1.0),
+        _ => 1.0,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Turn {
+    last: f32,
+    total: f32,
+}
+
+/// Twist of each contact, in degrees, clockwise positive, since it started. The
+/// orientation reported for a contact wraps every half turn, so changes are
+/// unwrapped and accumulated, allowing a twist to continue past the wrap
+#[derive(Debug, Clone, Default)]
+pub struct Twist {
+    turns: HashMap<u8, Turn>,
+}
+
+impl Twist {
+    pub fn new() -> Self {
+        Twist {
+            turns: HashMap::new(),
+        }
+    }
+
+    /// update with contact, returns its twist, 0 if ellipse is not enabled
+    pub fn update(&mut self, contact: &contact::Contact) -> f32 {
+        let orientation = match contact.ellipse {
+            Some(e) => e.orientation,
+            None => return 0.0,
+        };
+
+        let twist = match contact.state {
+            contact::State::CONTACT_START => {
+                self.turns.insert(contact.id, Turn { last: orientation, total: 0.0 });
+                0.0
+            },
+            _ => {
+                let turn = self.turns.entry(contact.id)
+                    .or_insert(Turn { last: orientation, total: 0.0 });
+                // shortest change, assuming less than a quarter turn between frames
+                let mut change = (orientation - turn.last) % HALF_TURN;
+                if change > HALF_TURN / 2.0 {
+                    change -= HALF_TURN;
+                }
+                else if change < -HALF_TURN / 2.0 {
+                    change += HALF_TURN;
+                }
+                turn.last = orientation;
+                turn.total += change;
+                turn.total
+            },
+        };
+
+        if contact.state == contact::State::CONTACT_END {
+            self.turns.remove(&contact.id);
+        }
+        twist
+    }
+}
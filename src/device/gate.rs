@@ -0,0 +1,292 @@
+//! Description:
+//!    Force thresholds and retrigger interval, applied alike to the contacts
+//!    of every controller type
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::net::{SocketAddrV4};
+use std::sync::mpsc::{Sender};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rosc::{OscPacket, OscMessage};
+
+use serde_json::Value;
+
+use crate::sensel::*;
+
+use super::controllers::*;
+
+/// distance within which a contact starting soon after another is taken as a
+/// bounce of it, rather than another finger
+const RETRIGGER_DISTANCE: f32 = 5.0;
+
+/// Thresholds as given in the JSON IR, either for a single controller or as
+/// layout wide defaults
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Thresholds {
+    /// force a contact must reach to start
+    pub min_force: Option<f32>,
+    /// force below which a started contact ends, defaults to min_force
+    pub release_force: Option<f32>,
+    /// minimum time between a contact starting and another starting at the same place
+    pub retrigger_ms: Option<u64>,
+}
+
+impl Thresholds {
+    /// thresholds, with any not given taken from defaults
+    pub fn or(self, defaults: Thresholds) -> Thresholds {
+        Thresholds {
+            min_force: self.min_force.or(defaults.min_force),
+            release_force: self.release_force.or(defaults.release_force),
+            retrigger_ms: self.retrigger_ms.or(defaults.retrigger_ms),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GateState {
+    /// yet to reach min force
+    Pending,
+    /// started, as far as the controller is concerned
+    Active,
+    /// fell below release force, can start again by reaching min force
+    Released,
+    /// started too soon after the previous contact, ignored until it ends
+    Ignored,
+}
+
+/// Wraps a controller, only passing on contacts that satisfy its thresholds.
+/// A contact is started, as far as the controller is concerned, once it reaches
+/// min force and ended if it drops below release force, giving hysteresis
+pub struct Gate {
+    inner: Box<Controller>,
+    min_force: f32,
+    release_force: f32,
+    retrigger: Duration,
+    /// time and position of recent starts passed to controller
+    starts: Vec<(Instant, f32, f32)>,
+    contacts: HashMap<u8, GateState>,
+}
+
+impl Gate {
+    pub fn new(inner: Box<Controller>, thresholds: Thresholds) -> Self {
+        let min_force = thresholds.min_force.unwrap_or(0.0);
+        Gate {
+            inner: inner,
+            min_force: min_force,
+            release_force: thresholds.release_force.unwrap_or(min_force),
+            retrigger: Duration::from_millis(thresholds.retrigger_ms.unwrap_or(0)),
+            starts: vec![],
+            contacts: HashMap::new(),
+        }
+    }
+
+    /// pass contact to controller, as if it were in the given state
+    fn forward(
+        &mut self,
+        contact: &contact::TrackedContact,
+        state: contact::State,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> Result<(), &'static str> {
+        let contact = contact.with_state(state);
+
+        match state {
+            contact::State::CONTACT_START => {
+                self.starts.push((Instant::now(), contact.x, contact.y));
+                self.inner.touch_start(&contact, transport)
+            },
+            contact::State::CONTACT_MOVE => {
+                self.inner.touch_move(&contact, transport);
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                self.inner.touch_end(&contact, transport);
+                Ok(())
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// true if contact can start, i.e. it is not a bounce of a contact that recently
+    /// started at the same place, other fingers start freely
+    fn retrigger(&mut self, contact: &contact::TrackedContact) -> bool {
+        let retrigger = self.retrigger;
+        self.starts.retain(|(time, _, _)| time.elapsed() < retrigger);
+        !self.starts.iter().any(|&(_, x, y)|
+            ((contact.x - x).powi(2) + (contact.y - y).powi(2)).sqrt() < RETRIGGER_DISTANCE)
+    }
+
+    fn process(
+        &mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> Result<(), &'static str> {
+        if contact.state == contact::State::CONTACT_START {
+            self.contacts.insert(contact.id, GateState::Pending);
+        }
+
+        let state = match self.contacts.get(&contact.id) {
+            Some(state) => *state,
+            None => return Ok(()),
+        };
+
+        if contact.state == contact::State::CONTACT_END {
+            self.contacts.remove(&contact.id);
+            if state == GateState::Active {
+                return self.forward(contact, contact::State::CONTACT_END, transport);
+            }
+            return Ok(());
+        }
+
+        match state {
+            GateState::Pending | GateState::Released => {
+                if contact.total_force >= self.min_force {
+                    let retrigger = self.retrigger(contact);
+                    if retrigger {
+                        self.contacts.insert(contact.id, GateState::Active);
+                        return self.forward(contact, contact::State::CONTACT_START, transport);
+                    }
+                    else if contact.state == contact::State::CONTACT_START {
+                        // a bounce, so ignore it altogether
+                        self.contacts.insert(contact.id, GateState::Ignored);
+                    }
+                }
+                Ok(())
+            },
+            GateState::Active => {
+                if contact.total_force < self.release_force {
+                    self.contacts.insert(contact.id, GateState::Released);
+                    return self.forward(contact, contact::State::CONTACT_END, transport);
+                }
+                self.forward(contact, contact::State::CONTACT_MOVE, transport)
+            },
+            GateState::Ignored => Ok(()),
+        }
+    }
+}
+
+impl Controller for Gate {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn touch_start(&mut self,
+             contact: &contact::TrackedContact,
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        self.process(contact, transport)
+    }
+
+    fn touch_move(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.process(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.process(contact, transport).is_ok()
+    }
+
+    fn tick(&mut self,
+        now: Instant,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        self.inner.tick(now, transport)
+    }
+
+    fn osc_message(&mut self,
+        msg: &OscMessage,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        self.inner.osc_message(msg, transport)
+    }
+
+    fn state(&self) -> Option<Value> {
+        self.inner.state()
+    }
+
+    fn set_state(&mut self,
+        state: &Value,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        self.inner.set_state(state, transport)
+    }
+
+    fn contact_mask(&self) -> contact::Mask {
+        self.inner.contact_mask()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::mpsc::channel;
+
+    /// counts starts passed on by gate
+    struct Starts(Rc<RefCell<u32>>);
+
+    impl Controller for Starts {
+        fn name(&self) -> &'static str {
+            "starts"
+        }
+
+        fn touch_start(&mut self,
+                 contact: &contact::TrackedContact,
+                 _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                    -> Result<(), &'static str> {
+            if contact.state == contact::State::CONTACT_START {
+                *self.0.borrow_mut() += 1;
+            }
+            Ok(())
+        }
+
+        fn touch_move(&mut self,
+            contact: &contact::TrackedContact,
+            transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+            self.touch_start(contact, transport).is_ok()
+        }
+
+        fn touch_end(&mut self,
+            contact: &contact::TrackedContact,
+            transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+            self.touch_start(contact, transport).is_ok()
+        }
+    }
+
+    fn contact(id: u8, state: contact::State, x: f32) -> contact::TrackedContact {
+        contact::TrackedContact::from(contact::Contact::at(id, state, x, 10.0))
+    }
+
+    fn gate() -> (Gate, Rc<RefCell<u32>>) {
+        let starts = Rc::new(RefCell::new(0));
+        let thresholds = Thresholds { min_force: None, release_force: None, retrigger_ms: Some(10000) };
+        (Gate::new(Box::new(Starts(starts.clone())), thresholds), starts)
+    }
+
+    #[test]
+    fn other_fingers_start_within_retrigger() {
+        let (mut gate, starts) = gate();
+        let (sender, _receiver) = channel();
+
+        gate.touch_start(&contact(1, contact::State::CONTACT_START, 10.0), &sender).unwrap();
+        gate.touch_start(&contact(2, contact::State::CONTACT_START, 30.0), &sender).unwrap();
+        gate.touch_end(&contact(1, contact::State::CONTACT_END, 10.0), &sender);
+        gate.touch_start(&contact(3, contact::State::CONTACT_START, 50.0), &sender).unwrap();
+        assert_eq!(*starts.borrow(), 3);
+    }
+
+    #[test]
+    fn bounce_is_ignored() {
+        let (mut gate, starts) = gate();
+        let (sender, _receiver) = channel();
+
+        gate.touch_start(&contact(1, contact::State::CONTACT_START, 10.0), &sender).unwrap();
+        gate.touch_end(&contact(1, contact::State::CONTACT_END, 10.0), &sender);
+        gate.touch_start(&contact(2, contact::State::CONTACT_START, 11.0), &sender).unwrap();
+        gate.touch_move(&contact(2, contact::State::CONTACT_MOVE, 11.0), &sender);
+        assert_eq!(*starts.borrow(), 1);
+    }
+}
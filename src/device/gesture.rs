@@ -0,0 +1,562 @@
+//! Description:
+//!    Gesture recognition, taps, double taps, long presses, multi-finger
+//!    swipes, pinches and two-finger rotation, recognised from contacts either
+//!    across the whole surface or within a controller's region, and mapped to
+//!    OSC messages by the layout
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::net::{SocketAddrV4};
+use std::sync::mpsc::{Sender};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use rosc::{OscPacket, OscMessage, OscType};
+
+use serde_json::Value;
+
+use crate::sensel::*;
+
+use super::controllers::*;
+use super::registry::BuildContext;
+
+/// once a pinch or rotation, ignore changes smaller than these
+const MIN_SCALE_CHANGE: f32 = 0.01;
+const MIN_ANGLE_CHANGE: f32 = 1.0;
+
+/// Direction of a swipe, with up towards the top of the surface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+impl Direction {
+    pub fn new(direction: &str) -> Result<Self, &'static str> {
+        match direction {
+            "left" => Ok(Direction::Left),
+            "right" => Ok(Direction::Right),
+            "up" => Ok(Direction::Up),
+            "down" => Ok(Direction::Down),
+            _ => Err("unknown swipe direction, expected left, right, up or down"),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Left => "left",
+            Direction::Right => "right",
+            Direction::Up => "up",
+            Direction::Down => "down",
+        }
+    }
+}
+
+/// Gesture recognised from contacts, positions are those of the contact
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Gesture {
+    Tap { x: f32, y: f32 },
+    /// sent in addition to the tap that completes it
+    DoubleTap { x: f32, y: f32 },
+    LongPress { x: f32, y: f32 },
+    Swipe { fingers: u32, direction: Direction },
+    /// distance between two fingers relative to when the second landed
+    Pinch { scale: f32 },
+    /// degrees, clockwise, two fingers have turned since the second landed
+    Rotate { angle: f32 },
+}
+
+impl Gesture {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Gesture::Tap { .. } => "tap",
+            Gesture::DoubleTap { .. } => "double_tap",
+            Gesture::LongPress { .. } => "long_press",
+            Gesture::Swipe { .. } => "swipe",
+            Gesture::Pinch { .. } => "pinch",
+            Gesture::Rotate { .. } => "rotate",
+        }
+    }
+}
+
+/// Timing and distance thresholds, as given in the JSON IR
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Thresholds {
+    /// longest touch that is a tap (default 200)
+    pub tap_ms: Option<u64>,
+    /// furthest a tap, or long press, can move (default 3)
+    pub tap_distance: Option<f32>,
+    /// longest time between taps of a double tap (default 300)
+    pub double_tap_ms: Option<u64>,
+    /// time a still touch is held for a long press (default 500)
+    pub long_press_ms: Option<u64>,
+    /// shortest distance, on average across fingers, that is a swipe (default 20)
+    pub swipe_distance: Option<f32>,
+    /// change in scale, either way, before two fingers are a pinch (default 0.1)
+    pub pinch_scale: Option<f32>,
+    /// degrees turned, either way, before two fingers are a rotation (default 10)
+    pub rotate_angle: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Touch {
+    start_x: f32,
+    start_y: f32,
+    start: Instant,
+    x: f32,
+    y: f32,
+    /// moved too far to be a tap or long press
+    moved: bool,
+}
+
+/// Two fingers, as they were when the second landed
+#[derive(Debug, Clone, Copy)]
+struct Pair {
+    ids: (u8, u8),
+    distance: f32,
+    angle: f32,
+    /// last scale and angle reported, None until pair is a pinch or rotation
+    scale: Option<f32>,
+    turned: Option<f32>,
+}
+
+/// Recognises gestures from contact events, a gesture runs from the first finger
+/// landing until all have lifted
+#[derive(Debug, Clone)]
+pub struct Recogniser {
+    tap: Duration,
+    tap_distance: f32,
+    double_tap: Duration,
+    long_press: Duration,
+    swipe_distance: f32,
+    pinch_scale: f32,
+    rotate_angle: f32,
+    touches: HashMap<u8, Touch>,
+    /// most fingers down at once during gesture
+    fingers: u32,
+    /// displacement of each finger that has lifted during gesture
+    lifted: Vec<(f32, f32)>,
+    /// gesture has been a long press, pinch or rotation, so is not a tap or swipe
+    claimed: bool,
+    pair: Option<Pair>,
+    /// time and position of last tap, for double taps
+    last_tap: Option<(Instant, f32, f32)>,
+}
+
+impl Recogniser {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Recogniser {
+            tap: Duration::from_millis(thresholds.tap_ms.unwrap_or(200)),
+            tap_distance: thresholds.tap_distance.unwrap_or(3.0),
+            double_tap: Duration::from_millis(thresholds.double_tap_ms.unwrap_or(300)),
+            long_press: Duration::from_millis(thresholds.long_press_ms.unwrap_or(500)),
+            swipe_distance: thresholds.swipe_distance.unwrap_or(20.0),
+            pinch_scale: thresholds.pinch_scale.unwrap_or(0.1),
+            rotate_angle: thresholds.rotate_angle.unwrap_or(10.0),
+            touches: HashMap::new(),
+            fingers: 0,
+            lifted: vec![],
+            claimed: false,
+            pair: None,
+            last_tap: None,
+        }
+    }
+
+    /// distance and angle, in degrees, from one touch to another
+    fn span(a: &Touch, b: &Touch) -> (f32, f32) {
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        ((dx * dx + dy * dy).sqrt(), dy.atan2(dx).to_degrees())
+    }
+
+    /// pair of touches, if exactly two are down
+    fn pair(&self) -> Option<Pair> {
+        if self.touches.len() != 2 {
+            return None;
+        }
+        let mut ids: Vec<u8> = self.touches.keys().cloned().collect();
+        ids.sort();
+        let (distance, angle) = Recogniser::span(&self.touches[&ids[0]], &self.touches[&ids[1]]);
+        Some(Pair {
+            ids: (ids[0], ids[1]),
+            distance: distance,
+            angle: angle,
+            scale: None,
+            turned: None,
+        })
+    }
+
+    /// pinch and rotation of pair since it started
+    fn pinch(&mut self, gestures: &mut Vec<Gesture>) {
+        let mut pair = match self.pair {
+            Some(pair) => pair,
+            None => return,
+        };
+        let (distance, angle) = match (self.touches.get(&pair.ids.0), self.touches.get(&pair.ids.1)) {
+            (Some(a), Some(b)) => Recogniser::span(a, b),
+            _ => return,
+        };
+
+        // jitter between two fingers, e.g. of a two finger tap or swipe, is not a
+        // pinch or rotation, which must first pass a larger threshold
+        if pair.distance > 0.0 {
+            let scale = distance / pair.distance;
+            let report = match pair.scale {
+                Some(last) => (scale - last).abs() >= MIN_SCALE_CHANGE,
+                None => (scale - 1.0).abs() >= self.pinch_scale,
+            };
+            if report {
+                pair.scale = Some(scale);
+                self.claimed = true;
+                gestures.push(Gesture::Pinch { scale: scale });
+            }
+        }
+
+        // y increases down the surface, so angles increase clockwise
+        let mut turned = angle - pair.angle;
+        if turned > 180.0 {
+            turned -= 360.0;
+        }
+        else if turned < -180.0 {
+            turned += 360.0;
+        }
+        let report = match pair.turned {
+            Some(last) => (turned - last).abs() >= MIN_ANGLE_CHANGE,
+            None => turned.abs() >= self.rotate_angle,
+        };
+        if report {
+            pair.turned = Some(turned);
+            self.claimed = true;
+            gestures.push(Gesture::Rotate { angle: turned });
+        }
+
+        self.pair = Some(pair);
+    }
+
+    /// all fingers have lifted, so gesture is complete
+    fn complete(&mut self, now: Instant, touch: &Touch, gestures: &mut Vec<Gesture>) {
+        let fingers = self.fingers;
+        let n = self.lifted.len() as f32;
+        let (dx, dy) = self.lifted.drain(..).fold((0.0, 0.0), |(x, y), (dx, dy)| (x + dx, y + dy));
+        let (dx, dy) = (dx / n, dy / n);
+        self.fingers = 0;
+
+        if self.claimed {
+            self.claimed = false;
+            return;
+        }
+
+        if fingers == 1 && !touch.moved && now.duration_since(touch.start) <= self.tap {
+            gestures.push(Gesture::Tap { x: touch.x, y: touch.y });
+            let double = self.last_tap.map_or(false, |(time, x, y)| {
+                now.duration_since(time) <= self.double_tap &&
+                ((touch.x - x).powi(2) + (touch.y - y).powi(2)).sqrt() <= self.tap_distance
+            });
+            if double {
+                gestures.push(Gesture::DoubleTap { x: touch.x, y: touch.y });
+                self.last_tap = None;
+            }
+            else {
+                self.last_tap = Some((now, touch.x, touch.y));
+            }
+        }
+        else if (dx * dx + dy * dy).sqrt() >= self.swipe_distance {
+            let direction = if dx.abs() >= dy.abs() {
+                if dx < 0.0 { Direction::Left } else { Direction::Right }
+            }
+            else {
+                if dy < 0.0 { Direction::Up } else { Direction::Down }
+            };
+            gestures.push(Gesture::Swipe { fingers: fingers, direction: direction });
+        }
+    }
+
+    /// process contact, returns any gestures recognised
+    pub fn contact(&mut self, contact: &contact::TrackedContact, now: Instant) -> Vec<Gesture> {
+        let mut gestures = vec![];
+        match contact.state {
+            contact::State::CONTACT_START => {
+                self.touches.insert(contact.id, Touch {
+                    start_x: contact.x,
+                    start_y: contact.y,
+                    start: now,
+                    x: contact.x,
+                    y: contact.y,
+                    moved: false,
+                });
+                self.fingers = self.fingers.max(self.touches.len() as u32);
+                self.pair = self.pair();
+            },
+            contact::State::CONTACT_MOVE => {
+                let tap_distance = self.tap_distance;
+                if let Some(touch) = self.touches.get_mut(&contact.id) {
+                    touch.x = contact.x;
+                    touch.y = contact.y;
+                    let (dx, dy) = (touch.x - touch.start_x, touch.y - touch.start_y);
+                    touch.moved = touch.moved || (dx * dx + dy * dy).sqrt() > tap_distance;
+                }
+                self.pinch(&mut gestures);
+            },
+            contact::State::CONTACT_END => {
+                if let Some(mut touch) = self.touches.remove(&contact.id) {
+                    touch.x = contact.x;
+                    touch.y = contact.y;
+                    self.lifted.push((touch.x - touch.start_x, touch.y - touch.start_y));
+                    // a pinch ends as soon as either finger lifts
+                    self.pair = self.pair();
+                    if self.touches.is_empty() {
+                        self.complete(now, &touch, &mut gestures);
+                    }
+                }
+            },
+            _ => { },
+        }
+        gestures
+    }
+
+    /// long presses are recognised while the touch is held, so need a regular tick
+    pub fn tick(&mut self, now: Instant) -> Vec<Gesture> {
+        if self.claimed || self.fingers != 1 {
+            return vec![];
+        }
+        match self.touches.values().next() {
+            Some(touch) if !touch.moved && now.duration_since(touch.start) >= self.long_press => {
+                self.claimed = true;
+                vec![Gesture::LongPress { x: touch.x, y: touch.y }]
+            },
+            _ => vec![],
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Mapping of a gesture to an OSC message, as given in the JSON IR
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GestureMapping {
+    /// tap, double_tap, long_press, swipe, pinch or rotate
+    pub gesture: String,
+    /// number of fingers a swipe must have, any if not given
+    pub fingers: Option<u32>,
+    /// direction a swipe must have, any if not given, when it is sent as an argument
+    pub direction: Option<String>,
+    pub address: String,
+    #[serde(default)]
+    pub args: Vec<ArgType>,
+}
+
+/// Gestures section of the JSON IR, either of the layout or a gesture controller
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GesturesConfig {
+    #[serde(flatten)]
+    pub thresholds: Thresholds,
+    #[serde(default)]
+    pub mappings: Vec<GestureMapping>,
+}
+
+#[derive(Debug, Clone)]
+struct Mapping {
+    gesture: String,
+    fingers: Option<u32>,
+    direction: Option<Direction>,
+    address: String,
+    args: Vec<OscType>,
+}
+
+/// build gesture controller from its JSON IR
+pub fn factory(json: &Value, context: &BuildContext) -> Result<Box<Controller>, &'static str> {
+    let config: GesturesConfig = serde_json::from_value(json.clone())
+        .map_err(|_| "unexpected format error with gesture controller")?;
+    Ok(Box::new(Gestures::new(config, context.region)?))
+}
+
+/// surface wide gestures, if the layout gives any
+pub fn surface(obj: &serde_json::Map<String, Value>, buffer: &Vec<Vec<u32>>) -> Result<Option<Gestures>, &'static str> {
+    match obj.get("gestures") {
+        Some(config) => {
+            let config: GesturesConfig = serde_json::from_value(config.clone())
+                .map_err(|_| "unexpected format error with gestures")?;
+            let region = Region {
+                x: 0.0,
+                y: 0.0,
+                width: buffer.len() as f32,
+                height: buffer.first().map_or(0, |column| column.len()) as f32,
+            };
+            Ok(Some(Gestures::new(config, region)?))
+        },
+        None => Ok(None),
+    }
+}
+
+/// Gesture controller
+///  Recognises gestures made within its region and sends the message of each mapping
+/// that matches, with the static arguments followed by, for taps and long presses,
+/// the position within the region, for swipes the number of fingers and, if the
+/// mapping does not give one, the direction, for pinches the scale and for rotation
+/// the angle in degrees
+#[derive(Debug, Clone)]
+pub struct Gestures {
+    /// area of Morph covered by gestures
+    region: Region,
+    recogniser: Recogniser,
+    mappings: Vec<Mapping>,
+}
+
+impl Gestures {
+    pub fn new(config: GesturesConfig, region: Region) -> Result<Self, &'static str> {
+        let mut mappings = vec![];
+        for mapping in config.mappings {
+            match mapping.gesture.as_str() {
+                "tap" | "double_tap" | "long_press" | "swipe" | "pinch" | "rotate" => { },
+                _ => return Err("unknown gesture, expected tap, double_tap, long_press, swipe, pinch or rotate"),
+            }
+            mappings.push(Mapping {
+                gesture: mapping.gesture,
+                fingers: mapping.fingers,
+                direction: match mapping.direction {
+                    Some(direction) => Some(Direction::new(&direction)?),
+                    None => None,
+                },
+                address: mapping.address,
+                args: mapping.args.into_iter().map(|a| OscType::from(a)).collect(),
+            });
+        }
+
+        Ok(Gestures {
+            region: region,
+            recogniser: Recogniser::new(config.thresholds),
+            mappings: mappings,
+        })
+    }
+
+    /// send messages of mappings that match gestures
+    fn send(&self, gestures: Vec<Gesture>, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        for gesture in gestures {
+            for mapping in self.mappings.iter().filter(|m| m.gesture == gesture.name()) {
+                let mut args = mapping.args.clone();
+                match gesture {
+                    Gesture::Tap { x, y } | Gesture::DoubleTap { x, y } | Gesture::LongPress { x, y } => {
+                        args.push(OscType::Float(self.region.norm_x(x)));
+                        args.push(OscType::Float(self.region.norm_y(y)));
+                    },
+                    Gesture::Swipe { fingers, direction } => {
+                        if mapping.fingers.map_or(false, |f| f != fingers) ||
+                           mapping.direction.map_or(false, |d| d != direction) {
+                            continue;
+                        }
+                        args.push(OscType::Int(fingers as i32));
+                        if mapping.direction.is_none() {
+                            args.push(OscType::String(direction.name().to_string()));
+                        }
+                    },
+                    Gesture::Pinch { scale } => args.push(OscType::Float(scale)),
+                    Gesture::Rotate { angle } => args.push(OscType::Float(angle)),
+                }
+
+                let packet = OscPacket::Message(OscMessage {
+                    addr: mapping.address.clone(),
+                    args: Some(args),
+                });
+                transport.send((packet, None)).unwrap();
+            }
+        }
+    }
+
+    /// process contact, which need not be within region, e.g. for surface wide gestures
+    pub fn contact(&mut self, contact: &contact::TrackedContact, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let gestures = self.recogniser.contact(contact, Instant::now());
+        self.send(gestures, transport);
+    }
+}
+
+impl Controller for Gestures {
+    fn name(&self) -> &'static str {
+        "gesture"
+    }
+
+    fn touch_start(&mut self,
+             contact: &contact::TrackedContact,
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        self.contact(contact, transport);
+        Ok(())
+    }
+
+    fn touch_move(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn tick(&mut self,
+        now: Instant,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        let gestures = self.recogniser.tick(now);
+        self.send(gestures, transport);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(id: u8, state: contact::State, x: f32, y: f32) -> contact::TrackedContact {
+        contact::TrackedContact::from(contact::Contact::at(id, state, x, y))
+    }
+
+    /// two fingers, 20 apart, moving by dx each step, with jitter j in their spacing
+    fn two_fingers(recogniser: &mut Recogniser, steps: &[(f32, f32)], end_ms: u64) -> Vec<Gesture> {
+        let start = Instant::now();
+        let mut gestures = vec![];
+        gestures.extend(recogniser.contact(&contact(1, contact::State::CONTACT_START, 50.0, 50.0), start));
+        gestures.extend(recogniser.contact(&contact(2, contact::State::CONTACT_START, 50.0, 70.0), start));
+        let (mut x, mut jitter) = (50.0, 0.0);
+        for (i, &(dx, j)) in steps.iter().enumerate() {
+            let now = start + Duration::from_millis(10 * (i as u64 + 1));
+            x += dx;
+            jitter = j;
+            gestures.extend(recogniser.contact(&contact(1, contact::State::CONTACT_MOVE, x, 50.0), now));
+            gestures.extend(recogniser.contact(&contact(2, contact::State::CONTACT_MOVE, x, 70.0 + jitter), now));
+        }
+        let end = start + Duration::from_millis(end_ms);
+        gestures.extend(recogniser.contact(&contact(1, contact::State::CONTACT_END, x, 50.0), end));
+        gestures.extend(recogniser.contact(&contact(2, contact::State::CONTACT_END, x, 70.0 + jitter), end));
+        gestures
+    }
+
+    #[test]
+    fn two_finger_swipe_with_jitter() {
+        let mut recogniser = Recogniser::new(Thresholds::default());
+        // fingers move a couple of units a frame, spacing jittering by up to a unit
+        let steps: Vec<(f32, f32)> = (0..15).map(|i| (-2.0, if i % 2 == 0 { 0.8 } else { -0.8 })).collect();
+        let gestures = two_fingers(&mut recogniser, &steps, 200);
+        assert_eq!(gestures, vec![Gesture::Swipe { fingers: 2, direction: Direction::Left }]);
+    }
+
+    #[test]
+    fn two_finger_tap_with_jitter_is_not_a_pinch() {
+        let mut recogniser = Recogniser::new(Thresholds::default());
+        let steps = [(0.0, 0.4), (0.0, -0.5)];
+        let gestures = two_fingers(&mut recogniser, &steps, 100);
+        assert!(gestures.iter().all(|g| g.name() != "pinch" && g.name() != "rotate"));
+    }
+
+    #[test]
+    fn pinch_past_threshold() {
+        let mut recogniser = Recogniser::new(Thresholds::default());
+        // spacing grows from 20 to 26, then by a further 1
+        let steps = [(0.0, 1.0), (0.0, 6.0), (0.0, 7.0)];
+        let gestures = two_fingers(&mut recogniser, &steps, 100);
+        assert_eq!(gestures, vec![Gesture::Pinch { scale: 1.3 }, Gesture::Pinch { scale: 1.35 }]);
+    }
+}
@@ -7,15 +7,20 @@
 use std::net::{SocketAddrV4};
 
 use super::controllers::*;
+use super::layout;
+use super::preset::{self, SharedPresets};
+use super::gesture::{self, Gestures};
+use super::rejection::{self, Rejection, Verdict};
+use super::smoothing::{self, Smoother};
+use super::registry::{self, Registry};
 
 use std::fs;
 
-use serde::{Deserialize, Serialize};
 use serde_json::{Value};
 
 use std::str::FromStr;
 use std::time::Duration;
-use std::sync::mpsc::{Sender};
+use std::sync::mpsc::{Sender, Receiver};
 
 use std::{thread, time};
 
@@ -34,44 +39,88 @@ use crate::sensel::device::Device;
 //-----------------------------------------------------------------------------
 // constants
 
-const TYPE_PAD : &'static str = "pad";
-const TYPE_DPAD : &'static str = "dpad";
-const TYPE_HSLIDER : &'static str = "horz_slider";
-const TYPE_VSLIDER : &'static str = "vert_slider"; 
-const TYPE_ENDLESS : &'static str = "endless";
-
 const NONE_ID: ID = 0;
 
 //-----------------------------------------------------------------------------
 
+const MAX_NUM_IDS: usize = 16;
+
 type ID = u32;
 
 pub struct Interface {
     buffer: Vec<Vec<ID>>,
     controls: Vec<Box<Controller>>,
     device: sensel::device::BaseDevice,
+    owners: [Option<ID>; MAX_NUM_IDS],
+    osc_input: Option<Receiver<OscPacket>>,
+    presets: SharedPresets,
+    /// surface wide gestures, if the layout gives any
+    gestures: Option<Gestures>,
+    /// palm and accidental touch rejection, if the layout gives any
+    rejection: Option<Rejection>,
+    /// layout wide smoothing, if the layout gives any
+    smoother: Option<Smoother>,
 }
 
 impl Interface {
     pub fn new(
         buffer: Vec<Vec<ID>>, 
         controls: Vec<Box<Controller>>, 
-        device: sensel::device::BaseDevice) -> Self {
+        device: sensel::device::BaseDevice,
+        presets: SharedPresets,
+        gestures: Option<Gestures>,
+        rejection: Option<Rejection>,
+        smoother: Option<Smoother>) -> Self {
         Interface {
             buffer: buffer,
             controls: controls,
             device: device,
+            owners: [None; MAX_NUM_IDS],
+            osc_input: None,
+            presets: presets,
+            gestures: gestures,
+            rejection: rejection,
+            smoother: smoother,
         }
     }
 
+    /// snapshot of the state of all controllers
+    pub fn snapshot(&self) -> Value {
+        preset::snapshot(&self.controls)
+    }
+
+    /// restore controllers from a snapshot, sending their new values
+    pub fn restore(&mut self, snapshot: &Value, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        preset::restore(&mut self.controls, snapshot, transport)
+    }
+
+    /// contact data needed by the controllers and rejection rules of the layout
+    pub fn contact_mask(&self) -> sensel::contact::Mask {
+        let mask = self.rejection.as_ref().map_or(sensel::contact::Mask::empty(), |r| r.contact_mask());
+        self.controls.iter().fold(mask, |mask, control| mask | control.contact_mask())
+    }
+
+    /// incoming OSC packets, e.g. tempo changes, are read from input and passed to
+    /// controllers while running
+    pub fn set_osc_input(&mut self, input: Receiver<OscPacket>) {
+        self.osc_input = Some(input);
+    }
+
     /// Process Morph data, returns only on exit
     pub fn run(mut self, hetz: u32, transport: Sender<(OscPacket, Option<SocketAddrV4>)>, disconnect: &AtomicBool) {
         //let d: Box<Device> = Box::new(self.device._get_device());
 
         self.device.set_frame_content(sensel::frame::Mask::CONTACTS).unwrap();
+        // only ask for the contact data the layout uses
+        let mask = self.contact_mask();
+        info!("Contacts mask: {:?}", mask);
+        self.device.set_contacts_mask(mask).unwrap();
 
         let scan = self.device.start_scanning().unwrap();
 
+        // history of each contact, for controllers that follow its motion
+        let mut tracker = sensel::contact::ContactTracker::new();
+
         // target duration of a single, i.e. run at the speed specified by caller
         let frame_duration_ms = time::Duration::from_millis((1000.0 / hetz as f32) as u64);
 
@@ -79,6 +128,30 @@ impl Interface {
             // read current time
             let now = time::Instant::now();
 
+            if let Some(input) = &self.osc_input {
+                for packet in input.try_iter() {
+                    for msg in osc_messages(&packet) {
+                        if self.presets.borrow_mut().osc_message(msg) {
+                            continue;
+                        }
+                        for control in self.controls.iter_mut() {
+                            control.osc_message(msg, &transport);
+                        }
+                    }
+                }
+            }
+
+            // controllers with their own clock run independently of contacts
+            for control in self.controls.iter_mut() {
+                control.tick(now, &transport);
+            }
+            if let Some(gestures) = &mut self.gestures {
+                gestures.tick(now, &transport);
+            }
+
+            // presets recalled by controllers or over OSC, and any morph in progress
+            self.presets.borrow_mut().update(now, &mut self.controls, &transport);
+
             // read sensor image
             scan.read_sensor().unwrap();
             let num_frames = scan.get_num_available_frames().unwrap();
@@ -90,29 +163,83 @@ impl Interface {
                 if contacts.len() > 0 {
                     info!("Num Contacts: {}", contacts.len());
                     for &contact in contacts {
-                        let contact = sensel::contact::Contact::from(contact);
+                        // smoothed before anything sees the contact, so rejection, gestures and
+                        // controller lookup all see the same position
+                        let received = time::Instant::now();
+                        let mut contact = sensel::contact::Contact::from(contact);
+                        if let Some(smoother) = &mut self.smoother {
+                            smoother.smooth(&mut contact, received);
+                        }
+                        let contact = tracker.update(contact, received);
+
+                        // palms and accidental touches reach neither controllers nor gestures
+                        let outside = self.buffer[contact.x as usize][contact.y as usize] == NONE_ID;
+                        match self.rejection.as_mut().map_or(Verdict::Accept, |r| r.check(&contact, outside)) {
+                            Verdict::Accept => { },
+                            Verdict::Reject => continue,
+                            Verdict::Cancel => {
+                                // end contact wherever it was accepted
+                                let end = contact.with_state(sensel::contact::State::CONTACT_END);
+                                if let Some(gestures) = &mut self.gestures {
+                                    gestures.contact(&end, &transport);
+                                }
+                                let id = self.owners[contact.id as usize].take().unwrap_or(
+                                    self.buffer[contact.x as usize][contact.y as usize]);
+                                if id != NONE_ID {
+                                    self.controls[id as usize - 1].touch_end(&end, &transport);
+                                }
+                                continue;
+                            },
+                        }
+
+                        // surface wide gestures see every contact, whichever controller it is on
+                        if let Some(gestures) = &mut self.gestures {
+                            gestures.contact(&contact, &transport);
+                        }
                         info!(
                             "Contact ID: {} State: {:?} @Location({},{})", 
                             contact.id, contact.state, contact.x, contact.y);
                         //println!("Buffer ID: {}", self.buffer[contact.x as usize][contact.y as usize]);
 
-                        if self.buffer[contact.x as usize][contact.y as usize] != NONE_ID {
+                        // contacts are delivered to the controller they started on, even once
+                        // they have moved outside of its area
+                        let id = match contact.state {
+                            sensel::contact::State::CONTACT_START => {
+                                let id = self.buffer[contact.x as usize][contact.y as usize];
+                                self.owners[contact.id as usize] = if id != NONE_ID { Some(id) } else { None };
+                                id
+                            },
+                            sensel::contact::State::CONTACT_END => {
+                                self.owners[contact.id as usize].take().unwrap_or(
+                                    self.buffer[contact.x as usize][contact.y as usize])
+                            },
+                            _ => {
+                                self.owners[contact.id as usize].unwrap_or(
+                                    self.buffer[contact.x as usize][contact.y as usize])
+                            }
+                        };
+
+                        if id != NONE_ID {
                             info!("Hit({}) {} [{},{}]", 
-                                self.buffer[contact.x as usize][contact.y as usize],
-                                self.controls[self.buffer[contact.x as usize][contact.y as usize] as usize - 1].name(),
+                                id,
+                                self.controls[id as usize - 1].name(),
                                 contact.x, contact.y);
 
-                            match self.controls[self.buffer[contact.x as usize][contact.y as usize] as usize - 1].touch_start(
-                                &contact,
-                                &transport) {
-                                Ok(_) => {},
-                                Err(s) => error!("{}", s)
-                            };
                             match contact.state {
                                 sensel::contact::State::CONTACT_START => {
+                                    match self.controls[id as usize - 1].touch_start(
+                                        &contact,
+                                        &transport) {
+                                        Ok(_) => {},
+                                        Err(s) => error!("{}", s)
+                                    };
                                     //scan.device().set_led_brightness(contact.id, 100).unwrap();
                                 },
+                                sensel::contact::State::CONTACT_MOVE => {
+                                    self.controls[id as usize - 1].touch_move(&contact, &transport);
+                                },
                                 sensel::contact::State::CONTACT_END => {
+                                    self.controls[id as usize - 1].touch_end(&contact, &transport);
                                     //scan.device().set_led_brightness(contact.id, 0).unwrap();
                                 },
                                 _ => {}
@@ -141,28 +268,10 @@ impl Interface {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Control {
-    pub address: String,
-    pub args: Vec<ArgType>,
-    pub id: usize,
-    pub rgb:  Option<String>,
-    pub pressure: Option<bool>,
-    pub generate_move: Option<bool>,
-    pub generate_end: Option<bool>,
-    pub generate_coords: Option<bool>,
-    pub type_id: String,
-    pub on: Option<ArgType>,
-    pub off: Option<ArgType>,
-    pub min: Option<ArgType>,
-    pub max: Option<ArgType>,
-    pub initial: Option<ArgType>, 
-    pub incr: Option<ArgType>,
-}
-
 pub struct InterfaceBuilder {
     input: String,
     number_of_controllers: u32,
+    registry: Registry,
 }
 
 impl InterfaceBuilder {
@@ -170,9 +279,23 @@ impl InterfaceBuilder {
         InterfaceBuilder {
             input: input,
             number_of_controllers: 0,
+            registry: Registry::new(),
         }
     }
 
+    /// add a controller type, built by factory from its JSON IR object, alongside 
+    /// the built in types
+    pub fn register<F>(&mut self, type_id: &str, factory: F)
+        where F: Fn(&Value, &registry::BuildContext) -> Result<Box<Controller>, &'static str> + 'static {
+        self.registry.register(type_id, factory);
+    }
+
+    /// replace registry of controller types
+    pub fn with_registry(mut self, registry: Registry) -> Self {
+        self.registry = registry;
+        self
+    }
+
     /// connect to sensel if one present otherwise return error
     fn sensel_info(&self) -> Result<sensel::device::BaseDevice, &'static str> {
         let mut input = String::new();
@@ -218,85 +341,20 @@ impl InterfaceBuilder {
 
         let v : serde_json::Result<Value>  = serde_json::from_str(&self.input);
         match v {
-            Ok(Value::Object(obj)) => {
+            Ok(Value::Object(mut obj)) => {
                 //let interface = Interface::new();
 
+                // generators expand into buffer cells and controllers
+                layout::expand_generators(&mut obj)?;
+
                 if obj.contains_key("buffer") {
                     let buffer: Vec<Vec<u32>> =  serde_json::from_value(obj["buffer"].clone()).unwrap();
                     //println!("{} {}", buffer.len(), buffer[0].len());
-                    match obj.get("controllers") {
-                        Some(Value::Array(controllers)) => {
-                            let number_of_controllers = controllers.len();
-
-                            // create an ordered (on ID) list of controls
-                            let mut cs: Vec<Control> = vec![];
-                            for c in controllers {
-                                // TODO: add check for Error and return an error is so
-                                let ctl: Control = serde_json::from_value(c.clone()).expect(
-                                                                        "unxpected format error with controller");
-                                cs.push(ctl.clone());
-                            }
-                            cs.sort_by(|a, b| a.id.cmp(&b.id));
-                            
-                            // Each controller has a unique ID, between 0..number_of_controllers-1, which is 
-                            // used as a direct index into array of Controller instances
-                            let mut controls: Vec<Box<Controller>> = vec![];
-
-                            for ctl in cs {
-                                if ctl.type_id == TYPE_PAD {
-                                    let pressure = ctl.pressure.map_or(false, |_| true);
-                                    let generate_move = ctl.generate_move.map_or(false, |_| true);
-                                    let generate_end = ctl.generate_end.map_or(false, |_| true);
-                                    let generate_coords = ctl.generate_coords.map_or(false, |_| true);
-                                    
-                                    let pad = Box::new(
-                                        Pad::new(
-                                            ctl.address, 
-                                            ctl.args, 
-                                            pressure, 
-                                            generate_move, 
-                                            generate_end,
-                                            generate_coords));
-                                    info!("adding pad = {}", ctl.id);
-                                    controls.push(pad);
-                                }
-                                else if ctl.type_id == TYPE_DPAD {
-                                    let on = ctl.on.map_or(ArgType::IType(0), |x| x);
-                                    let off = ctl.off.map_or(ArgType::IType(0), |x| x);
-                                    
-                                    let pad = Box::new(
-                                        DPad::new(
-                                            ctl.address, 
-                                            on,
-                                            off,
-                                            ctl.args));
-                                    info!("adding dpad = {}", ctl.id);
-                                    controls.push(pad);
-                                }
-                                else if ctl.type_id == TYPE_VSLIDER { 
-                                    let vslider = Box::new(
-                                        VSlider::new(ctl.address, ctl.args, ctl.min, ctl.max, ctl.initial, ctl.incr));
-                                    info!("adding vslider = {}", ctl.id);
-                                    controls.push(vslider);
-                                }
-                                else if ctl.type_id == TYPE_HSLIDER {
-                                    let hslider = Box::new(
-                                        HSlider::new(ctl.address, ctl.args, ctl.min, ctl.max, ctl.initial, ctl.incr));
-                                    info!("hslider = {}", ctl.id);
-                                    controls.push(hslider);
-
-                                }
-                                else if ctl.type_id == TYPE_ENDLESS {
-                                    let endless = Box::new(Endless::new(ctl.address, ctl.args));
-                                    info!("endless = {}", ctl.id);
-                                    controls.push(endless);
-                                }
-                            }
-
-                            Ok(Interface::new(buffer, controls, device))
-                        },
-                        _ => Err("failed to find controllers array"), 
-                    }
+                    let (controls, presets) = registry::build_controls(&obj, &buffer, &self.registry)?;
+                    let gestures = gesture::surface(&obj, &buffer)?;
+                    let rejection = rejection::layout(&obj)?;
+                    let smoother = smoothing::layout(&obj)?;
+                    Ok(Interface::new(buffer, controls, device, presets, gestures, rejection, smoother))
                 }
                 else {
                     Err("failed to find buffer")
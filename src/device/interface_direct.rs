@@ -7,10 +7,15 @@
 use std::net::{SocketAddrV4};
 
 use super::controllers::*;
+use super::layout;
+use super::preset::{self, SharedPresets};
+use super::gesture::{self, Gestures};
+use super::rejection::{self, Rejection, Verdict};
+use super::smoothing::{self, Smoother};
+use super::registry::{self, Registry};
 
 use std::fs;
 
-use serde::{Deserialize, Serialize};
 use serde_json::{Value};
 
 use std::str::FromStr;
@@ -36,12 +41,6 @@ use crate::sensel::device::Device;
 //-----------------------------------------------------------------------------
 // constants
 
-const TYPE_PAD : &'static str = "pad";
-const TYPE_DPAD : &'static str = "dpad";
-const TYPE_HSLIDER : &'static str = "horz_slider";
-const TYPE_VSLIDER : &'static str = "vert_slider"; 
-const TYPE_ENDLESS : &'static str = "endless";
-
 const NONE_ID: ID = 0;
 
 //-----------------------------------------------------------------------------
@@ -54,24 +53,89 @@ pub struct InterfaceDirect {
     buffer: Vec<Vec<ID>>,
     controls: Vec<Box<Controller>>,
     move_end: [Option<ID>; MAX_NUM_IDS],
+    presets: SharedPresets,
+    /// surface wide gestures, if the layout gives any
+    gestures: Option<Gestures>,
+    /// palm and accidental touch rejection, if the layout gives any
+    rejection: Option<Rejection>,
+    /// layout wide smoothing, if the layout gives any
+    smoother: Option<Smoother>,
+    tracker: contact::ContactTracker,
 }
 
 impl InterfaceDirect {
     pub fn new(
         buffer: Vec<Vec<ID>>, 
-        controls: Vec<Box<Controller>>) -> Self {
+        controls: Vec<Box<Controller>>,
+        presets: SharedPresets,
+        gestures: Option<Gestures>,
+        rejection: Option<Rejection>,
+        smoother: Option<Smoother>) -> Self {
         InterfaceDirect {
             buffer: buffer,
             controls: controls,
             move_end: [None; MAX_NUM_IDS],
+            presets: presets,
+            gestures: gestures,
+            rejection: rejection,
+            smoother: smoother,
+            tracker: contact::ContactTracker::new(),
         }
     }
 
+    /// snapshot of the state of all controllers
+    pub fn snapshot(&self) -> Value {
+        preset::snapshot(&self.controls)
+    }
+
+    /// restore controllers from a snapshot, sending their new values
+    pub fn restore(&mut self, snapshot: &Value, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        preset::restore(&mut self.controls, snapshot, transport)
+    }
+
+    /// contact data needed by the controllers and rejection rules of the layout, which
+    /// the host should enable on the device with set_contacts_mask
+    pub fn contact_mask(&self) -> contact::Mask {
+        let mask = self.rejection.as_ref().map_or(contact::Mask::empty(), |r| r.contact_mask());
+        self.controls.iter().fold(mask, |mask, control| mask | control.contact_mask())
+    }
+
     /// process contact from external (sensel) interface 
     pub fn handleContact(
         &mut self,
         contact: &contact::Contact, 
         transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+            // smoothed before anything sees the contact, so rejection, gestures and
+            // controller lookup all see the same position
+            let now = time::Instant::now();
+            let mut contact = *contact;
+            if let Some(smoother) = &mut self.smoother {
+                smoother.smooth(&mut contact, now);
+            }
+            let contact = self.tracker.update(contact, now);
+
+            // palms and accidental touches reach neither controllers nor gestures
+            let outside = self.buffer[contact.x as usize][contact.y as usize] == NONE_ID;
+            match self.rejection.as_mut().map_or(Verdict::Accept, |r| r.check(&contact, outside)) {
+                Verdict::Accept => { },
+                Verdict::Reject => return,
+                Verdict::Cancel => {
+                    // end contact wherever it was accepted
+                    let end = contact.with_state(sensel::contact::State::CONTACT_END);
+                    if let Some(gestures) = &mut self.gestures {
+                        gestures.contact(&end, &transport);
+                    }
+                    if let Some(id) = self.move_end[contact.id as usize].take() {
+                        self.controls[id as usize - 1].touch_end(&end, &transport);
+                    }
+                    return;
+                },
+            }
+
+            // surface wide gestures see every contact, whichever controller it is on
+            if let Some(gestures) = &mut self.gestures {
+                gestures.contact(&contact, &transport);
+            }
             match contact.state {
                 sensel::contact::State::CONTACT_START => {
                     let id = self.buffer[contact.x as usize][contact.y as usize];
@@ -87,7 +151,9 @@ impl InterfaceDirect {
                     }
                 },
                 sensel::contact::State::CONTACT_MOVE => {
-
+                    if let Some(id) = self.move_end[contact.id as usize] {
+                        self.controls[id as usize - 1].touch_move(&contact, &transport);
+                    }
                 },
                 sensel::contact::State::CONTACT_END => {
                     if let Some(id) = self.move_end[contact.id as usize] {
@@ -99,6 +165,36 @@ impl InterfaceDirect {
                 },
             }
     }
+
+    /// advance controllers that keep their own time, and apply preset changes, should 
+    /// be called regularly by host
+    pub fn tick(
+        &mut self,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+            let now = time::Instant::now();
+            for control in self.controls.iter_mut() {
+                control.tick(now, &transport);
+            }
+            if let Some(gestures) = &mut self.gestures {
+                gestures.tick(now, &transport);
+            }
+            self.presets.borrow_mut().update(now, &mut self.controls, &transport);
+    }
+
+    /// process OSC packet received by host, e.g. tempo changes
+    pub fn handle_osc(
+        &mut self,
+        packet: &OscPacket,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+            for msg in osc_messages(packet) {
+                if self.presets.borrow_mut().osc_message(msg) {
+                    continue;
+                }
+                for control in self.controls.iter_mut() {
+                    control.osc_message(msg, &transport);
+                }
+            }
+    }
 }
 
     /// Process Morph data, returns only on exit
@@ -178,28 +274,10 @@ impl InterfaceDirect {
     // }
 // }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Control {
-    pub address: String,
-    pub args: Vec<ArgType>,
-    pub id: usize,
-    pub rgb:  Option<String>,
-    pub pressure: Option<bool>,
-    pub generate_move: Option<bool>,
-    pub generate_end: Option<bool>,
-    pub generate_coords: Option<bool>,
-    pub on: Option<ArgType>,
-    pub off: Option<ArgType>,
-    pub type_id: String,
-    pub min: Option<ArgType>,
-    pub max: Option<ArgType>,
-    pub initial: Option<ArgType>, 
-    pub incr: Option<ArgType>,
-}
-
 pub struct InterfaceBuilder {
     input: String,
     number_of_controllers: u32,
+    registry: Registry,
 }
 
 impl InterfaceBuilder {
@@ -207,9 +285,23 @@ impl InterfaceBuilder {
         InterfaceBuilder {
             input: input,
             number_of_controllers: 0,
+            registry: Registry::new(),
         }
     }
 
+    /// add a controller type, built by factory from its JSON IR object, alongside 
+    /// the built in types
+    pub fn register<F>(&mut self, type_id: &str, factory: F)
+        where F: Fn(&Value, &registry::BuildContext) -> Result<Box<Controller>, &'static str> + 'static {
+        self.registry.register(type_id, factory);
+    }
+
+    /// replace registry of controller types
+    pub fn with_registry(mut self, registry: Registry) -> Self {
+        self.registry = registry;
+        self
+    }
+
     /// build interface
     /// Loads JSON IR interface
     /// Connects to Sensel Morph
@@ -222,84 +314,20 @@ impl InterfaceBuilder {
 
         let v : serde_json::Result<Value>  = serde_json::from_str(&self.input);
         match v {
-            Ok(Value::Object(obj)) => {
+            Ok(Value::Object(mut obj)) => {
                 //let interface = Interface::new();
 
+                // generators expand into buffer cells and controllers
+                layout::expand_generators(&mut obj)?;
+
                 if obj.contains_key("buffer") {
                     let buffer: Vec<Vec<u32>> =  serde_json::from_value(obj["buffer"].clone()).unwrap();
                     //println!("{} {}", buffer.len(), buffer[0].len());
-                    match obj.get("controllers") {
-                        Some(Value::Array(controllers)) => {
-                            let number_of_controllers = controllers.len();
-
-                            // create an ordered (on ID) list of controls
-                            let mut cs: Vec<Control> = vec![];
-                            for c in controllers {
-                                // TODO: add check for Error and return an error is so
-                                let ctl: Control = serde_json::from_value(c.clone()).expect("unxpected format error with controller");
-                                cs.push(ctl.clone());
-                            }
-                            cs.sort_by(|a, b| a.id.cmp(&b.id));
-                            
-                            // Each controller has a unique ID, between 0..number_of_controllers-1, which is 
-                            // used as a direct index into array of Controller instances
-                            let mut controls: Vec<Box<Controller>> = vec![];
-
-                            for ctl in cs {
-                                if ctl.type_id == TYPE_PAD {
-                                    let pressure = ctl.pressure.map_or(false, |_| true);
-                                    let generate_move = ctl.generate_move.map_or(false, |_| true);
-                                    let generate_end = ctl.generate_end.map_or(false, |_| true);
-                                    let generate_coords = ctl.generate_coords.map_or(false, |_| true);
-                                    
-                                    let pad = Box::new(
-                                        Pad::new(
-                                            ctl.address, 
-                                            ctl.args, 
-                                            pressure, 
-                                            generate_move, 
-                                            generate_end,
-                                            generate_coords));
-                                    info!("adding pad = {}", ctl.id);
-                                    controls.push(pad);
-                                }
-                                else if ctl.type_id == TYPE_DPAD {
-                                    let on = ctl.on.map_or(ArgType::IType(0), |x| x);
-                                    let off = ctl.off.map_or(ArgType::IType(0), |x| x);
-                                    
-                                    let pad = Box::new(
-                                        DPad::new(
-                                            ctl.address, 
-                                            on,
-                                            off,
-                                            ctl.args));
-                                    info!("adding dpad = {}", ctl.id);
-                                    controls.push(pad);
-                                }
-                                else if ctl.type_id == TYPE_VSLIDER { 
-                                    let vslider = Box::new(
-                                        VSlider::new(ctl.address, ctl.args, ctl.min, ctl.max, ctl.initial, ctl.incr));
-                                    info!("adding vslider = {}", ctl.id);
-                                    controls.push(vslider);
-                                }
-                                else if ctl.type_id == TYPE_HSLIDER {
-                                    let hslider = Box::new(
-                                        HSlider::new(ctl.address, ctl.args, ctl.min, ctl.max, ctl.initial, ctl.incr));
-                                    info!("hslider = {}", ctl.id);
-                                    controls.push(hslider);
-
-                                }
-                                else if ctl.type_id == TYPE_ENDLESS {
-                                    let endless = Box::new(Endless::new(ctl.address, ctl.args));
-                                    info!("endless = {}", ctl.id);
-                                    controls.push(endless);
-                                }
-                            }
-
-                            Ok(InterfaceDirect::new(buffer, controls))
-                        },
-                        _ => Err("failed to find controllers array"), 
-                    }
+                    let (controls, presets) = registry::build_controls(&obj, &buffer, &self.registry)?;
+                    let gestures = gesture::surface(&obj, &buffer)?;
+                    let rejection = rejection::layout(&obj)?;
+                    let smoother = smoothing::layout(&obj)?;
+                    Ok(InterfaceDirect::new(buffer, controls, presets, gestures, rejection, smoother))
                 }
                 else {
                     Err("failed to find buffer")
@@ -0,0 +1,220 @@
+//! Description:
+//!    Expansion of layout generators, found in the JSON IR, into buffer cells
+//!    and controllers, before the interface is built
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::collections::HashSet;
+
+use serde_json::{Map, Value};
+
+use super::controllers::Region;
+use super::music::Isomorphic;
+
+//-----------------------------------------------------------------------------
+// constants
+
+const OUTPUT_PADS : &'static str = "pads";
+const OUTPUT_KEYBOARD : &'static str = "keyboard";
+
+//-----------------------------------------------------------------------------
+
+/// Isomorphic layout generator, any fields not listed here are copied into
+/// each generated controller
+#[derive(Debug, Clone, Deserialize)]
+struct Generator {
+    /// wicki_hayden, harmonic_table, fourths or custom
+    #[serde(rename = "type")]
+    layout: String,
+    root: Option<u8>,
+    col_interval: Option<i32>,
+    row_interval: Option<i32>,
+    row_offset: Option<f32>,
+    /// [width, height]
+    cell_size: (f32, f32),
+    /// [x, y, width, height]
+    region: (f32, f32, f32, f32),
+    /// pads (default) or keyboard
+    output: Option<String>,
+    #[serde(flatten)]
+    controller: Map<String, Value>,
+}
+
+/// assign id to all buffer cells within area
+fn fill(buffer: &mut Vec<Vec<u32>>, area: &Region, id: u32) {
+    for (x, column) in buffer.iter_mut().enumerate() {
+        if (x as f32) < area.x || x as f32 >= area.x + area.width {
+            continue;
+        }
+        for (y, cell) in column.iter_mut().enumerate() {
+            if y as f32 >= area.y && (y as f32) < area.y + area.height {
+                *cell = id;
+            }
+        }
+    }
+}
+
+/// Replace any generators in the IR with the buffer cells and controllers they
+/// describe. Generated controllers are given ids following those already in use
+pub fn expand_generators(obj: &mut Map<String, Value>) -> Result<(), &'static str> {
+    let generators = match obj.remove("generators") {
+        None => return Ok(()),
+        Some(Value::Array(generators)) => generators,
+        Some(_) => return Err("generators must be an array"),
+    };
+
+    let mut buffer: Vec<Vec<u32>> = match obj.get("buffer") {
+        Some(buffer) => serde_json::from_value(buffer.clone()).map_err(|_| "failed to pass buffer")?,
+        None => return Err("failed to find buffer"),
+    };
+
+    let mut controllers = match obj.remove("controllers") {
+        None => vec![],
+        Some(Value::Array(controllers)) => controllers,
+        Some(_) => return Err("failed to find controllers array"),
+    };
+
+    // ids of existing controllers and buffer cells, along with those generated
+    let mut used: HashSet<u32> = controllers.iter()
+        .filter_map(|c| c.get("id").and_then(|id| id.as_u64()))
+        .map(|id| id as u32)
+        .chain(buffer.iter().flat_map(|column| column.iter().cloned()))
+        .filter(|id| *id != 0)
+        .collect();
+    let mut next_id = used.iter().max().cloned().unwrap_or(0) + 1;
+
+    for generator in generators {
+        let generator: Generator = serde_json::from_value(generator)
+            .map_err(|_| "unexpected format error with generator")?;
+
+        let (col_interval, row_interval, row_offset) =
+            match (Isomorphic::preset(&generator.layout), generator.col_interval, generator.row_interval) {
+                (_, Some(col), Some(row)) => (col, row, generator.row_offset.unwrap_or(0.0)),
+                (Some((col, row, offset)), c, r) =>
+                    (c.unwrap_or(col), r.unwrap_or(row), generator.row_offset.unwrap_or(offset)),
+                _ => return Err("unknown generator type, row and column intervals must be given"),
+            };
+
+        let grid = Isomorphic {
+            root: generator.root.unwrap_or(48),
+            col_interval: col_interval,
+            row_interval: row_interval,
+            row_offset: row_offset,
+            cell_width: generator.cell_size.0,
+            cell_height: generator.cell_size.1,
+        };
+
+        let (x, y, width, height) = generator.region;
+        let region = Region { x: x, y: y, width: width, height: height };
+
+        let args = match generator.controller.get("args") {
+            Some(Value::Array(args)) => args.clone(),
+            _ => vec![],
+        };
+
+        match generator.output.as_ref().map_or(OUTPUT_PADS, |o| o.as_str()) {
+            OUTPUT_PADS => {
+                for row in 0..grid.rows(&region) {
+                    for col in 0..grid.columns(&region) {
+                        let note = grid.note(col, row);
+                        if note < 0 || note > 127 {
+                            continue;
+                        }
+
+                        let id = next_id;
+                        next_id += 1;
+                        used.insert(id);
+                        fill(&mut buffer, &grid.cell_region(&region, col, row), id);
+
+                        // note is passed as the first controller argument
+                        let mut pad_args = vec![Value::from(note)];
+                        pad_args.extend(args.iter().cloned());
+
+                        let mut pad = generator.controller.clone();
+                        pad.insert("type_id".to_string(), Value::from("pad"));
+                        pad.insert("id".to_string(), Value::from(id));
+                        pad.insert("args".to_string(), Value::Array(pad_args));
+                        controllers.push(Value::Object(pad));
+                    }
+                }
+            },
+            OUTPUT_KEYBOARD => {
+                let id = match generator.controller.get("id").and_then(|id| id.as_u64()) {
+                    Some(id) => id as u32,
+                    None => next_id,
+                };
+                if id == 0 || !used.insert(id) {
+                    return Err("generator keyboard id is already in use");
+                }
+                next_id = next_id.max(id + 1);
+                fill(&mut buffer, &region, id);
+
+                let mut keyboard = generator.controller.clone();
+                keyboard.insert("type_id".to_string(), Value::from("keyboard"));
+                keyboard.insert("id".to_string(), Value::from(id));
+                keyboard.insert("args".to_string(), Value::Array(args));
+                keyboard.insert("grid".to_string(), serde_json::to_value(grid).unwrap());
+                controllers.push(Value::Object(keyboard));
+            },
+            _ => return Err("generator output must be pads or keyboard"),
+        }
+    }
+
+    obj.insert("buffer".to_string(), serde_json::to_value(buffer).unwrap());
+    obj.insert("controllers".to_string(), Value::Array(controllers));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn layout(controllers: Value, generators: Value) -> Map<String, Value> {
+        match json!({
+            "buffer": [[0, 0, 0, 0], [0, 0, 0, 0], [1, 1, 0, 0], [1, 1, 0, 0]],
+            "controllers": controllers,
+            "generators": generators,
+        }) {
+            Value::Object(obj) => obj,
+            _ => unreachable!(),
+        }
+    }
+
+    fn keyboard(id: Option<u32>, x: f32) -> Value {
+        let mut keyboard = json!({
+            "type": "wicki_hayden",
+            "cell_size": [1, 1],
+            "region": [x, 0, 1, 2],
+            "output": "keyboard",
+            "address": "/keyboard",
+            "args": [],
+        });
+        if let Some(id) = id {
+            keyboard["id"] = Value::from(id);
+        }
+        keyboard
+    }
+
+    fn ids(obj: &Map<String, Value>) -> Vec<u64> {
+        obj["controllers"].as_array().unwrap().iter().map(|c| c["id"].as_u64().unwrap()).collect()
+    }
+
+    #[test]
+    fn generated_ids_follow_explicit_id() {
+        let mut obj = layout(json!([{ "id": 1 }]), json!([keyboard(Some(3), 0.0), keyboard(None, 1.0)]));
+        expand_generators(&mut obj).unwrap();
+        assert_eq!(ids(&obj), vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn explicit_id_in_use_is_rejected() {
+        let mut obj = layout(json!([{ "id": 1 }]), json!([keyboard(Some(1), 0.0)]));
+        assert!(expand_generators(&mut obj).is_err());
+
+        let mut obj = layout(json!([{ "id": 1 }]), json!([keyboard(None, 0.0), keyboard(Some(2), 1.0)]));
+        assert!(expand_generators(&mut obj).is_err());
+    }
+}
@@ -5,5 +5,21 @@
 //! 
 
 pub mod controllers;
+pub mod gate;
+pub mod smoothing;
+pub mod route;
+pub mod osc_input;
+pub mod preset;
+pub mod modifier;
+pub mod registry;
+pub mod envelope;
+pub mod gesture;
+pub mod rejection;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod music;
+pub mod template;
+pub mod expression;
 pub mod interface;
 pub mod interface_direct;
+pub mod layout;
@@ -0,0 +1,296 @@
+//! Description:
+//!    Modifier controllers, e.g. shift, and controllers that switch to an
+//!    alternate behaviour while a modifier is engaged
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::net::{SocketAddrV4};
+use std::sync::mpsc::{Sender, Receiver, channel};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Instant;
+
+use rosc::{OscPacket, OscMessage, OscType};
+
+use serde_json::Value;
+
+use crate::sensel::*;
+
+use super::controllers::*;
+
+/// Number of modifier controllers currently engaging each named modifier,
+/// shared by all controllers of an interface
+pub type Modifiers = Rc<RefCell<HashMap<String, u32>>>;
+
+/// true if the named modifier is engaged
+pub fn engaged(modifiers: &Modifiers, name: &str) -> bool {
+    modifiers.borrow().get(name).map_or(false, |count| *count > 0)
+}
+
+/// Modifier controller
+///  Engages its modifier while held or, when latching, from one press to the next,
+/// sending the static arguments followed by 1 when engaged and 0 when released
+#[derive(Debug, Clone)]
+pub struct Modifier {
+    /// OSC address
+    address: String,
+    /// static OSC message arguments
+    args: Vec<OscType>,
+    /// name of modifier
+    modifier: String,
+    latch: bool,
+    modifiers: Modifiers,
+    engaged: bool,
+    /// contacts holding modifier
+    contacts: Vec<u8>,
+}
+
+impl Modifier {
+    pub fn new(
+        address: String, args: Vec<ArgType>, modifier: Option<String>,
+        latch: Option<bool>, modifiers: Modifiers) -> Result<Self, &'static str> {
+        Ok(Modifier {
+            address: address,
+            args: args.into_iter().map(|a| OscType::from(a)).collect(),
+            modifier: modifier.ok_or("modifier controller must name its modifier")?,
+            latch: latch.unwrap_or(false),
+            modifiers: modifiers,
+            engaged: false,
+            contacts: vec![],
+        })
+    }
+
+    fn engage(&mut self, engaged: bool, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        if engaged == self.engaged {
+            return;
+        }
+        self.engaged = engaged;
+
+        {
+            let mut modifiers = self.modifiers.borrow_mut();
+            let count = modifiers.entry(self.modifier.clone()).or_insert(0);
+            if engaged {
+                *count += 1;
+            }
+            else if *count > 0 {
+                *count -= 1;
+            }
+        }
+
+        let mut args = self.args.clone();
+        args.push(OscType::Int(if engaged { 1 } else { 0 }));
+        let packet = OscPacket::Message(OscMessage {
+            addr: self.address.clone(),
+            args: Some(args),
+        });
+        transport.send((packet, None)).unwrap();
+    }
+}
+
+impl Controller for Modifier {
+    fn name(&self) -> &'static str {
+        "modifier"
+    }
+
+    fn touch_start(&mut self,
+             contact: &contact::TrackedContact,
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        match contact.state {
+            contact::State::CONTACT_START => {
+                if self.latch {
+                    let engaged = !self.engaged;
+                    self.engage(engaged, transport);
+                }
+                else {
+                    self.contacts.push(contact.id);
+                    self.engage(true, transport);
+                }
+                Ok(())
+            },
+            contact::State::CONTACT_END => {
+                if !self.latch {
+                    self.contacts.retain(|id| *id != contact.id);
+                    if self.contacts.is_empty() {
+                        self.engage(false, transport);
+                    }
+                }
+                Ok(())
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+
+    fn touch_move(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Wraps a controller and an alternate instance of it, built from the overrides
+/// given by the controller's alt in the JSON IR. Contacts starting while the
+/// modifier is engaged go to the alternate, and stay with the instance they started
+/// on. On switching, the state of one instance is passed to the other, so e.g. a
+/// slider's value carries over to its fine adjustment alternate
+pub struct Modified {
+    normal: Box<Controller>,
+    alt: Box<Controller>,
+    /// name of modifier
+    modifier: String,
+    modifiers: Modifiers,
+    /// true if the alternate is the instance in use
+    using_alt: bool,
+    /// instance each contact started on, true if alternate
+    contacts: HashMap<u8, bool>,
+    /// messages sent when passing state between instances, or by the instance not
+    /// in use as it ticks, are discarded
+    quiet_sender: Sender<(OscPacket, Option<SocketAddrV4>)>,
+    quiet_receiver: Receiver<(OscPacket, Option<SocketAddrV4>)>,
+}
+
+impl Modified {
+    pub fn new(
+        normal: Box<Controller>, alt: Box<Controller>,
+        modifier: String, modifiers: Modifiers) -> Self {
+        let (quiet_sender, quiet_receiver) = channel();
+        Modified {
+            normal: normal,
+            alt: alt,
+            modifier: modifier,
+            modifiers: modifiers,
+            using_alt: false,
+            contacts: HashMap::new(),
+            quiet_sender: quiet_sender,
+            quiet_receiver: quiet_receiver,
+        }
+    }
+
+    fn instance(&mut self, alt: bool) -> &mut Box<Controller> {
+        if alt { &mut self.alt } else { &mut self.normal }
+    }
+
+    /// switch instance in use, passing on state
+    fn switch(&mut self, alt: bool) {
+        let state = self.instance(self.using_alt).state();
+        if let Some(state) = state {
+            let quiet = self.quiet_sender.clone();
+            self.instance(alt).set_state(&state, &quiet);
+            self.quiet_receiver.try_iter().for_each(drop);
+        }
+        self.using_alt = alt;
+    }
+
+    fn process(
+        &mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> Result<(), &'static str> {
+        match contact.state {
+            contact::State::CONTACT_START => {
+                // only switch once contacts on the instance in use have lifted
+                let alt = engaged(&self.modifiers, &self.modifier);
+                if alt != self.using_alt && self.contacts.is_empty() {
+                    self.switch(alt);
+                }
+                let alt = self.using_alt;
+                self.contacts.insert(contact.id, alt);
+                self.instance(alt).touch_start(contact, transport)
+            },
+            contact::State::CONTACT_MOVE => {
+                match self.contacts.get(&contact.id).cloned() {
+                    Some(alt) => {
+                        self.instance(alt).touch_move(contact, transport);
+                        Ok(())
+                    },
+                    None => Ok(()),
+                }
+            },
+            contact::State::CONTACT_END => {
+                match self.contacts.remove(&contact.id) {
+                    Some(alt) => {
+                        self.instance(alt).touch_end(contact, transport);
+                        Ok(())
+                    },
+                    None => Ok(()),
+                }
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Controller for Modified {
+    fn name(&self) -> &'static str {
+        self.normal.name()
+    }
+
+    fn touch_start(&mut self,
+             contact: &contact::TrackedContact,
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        self.process(contact, transport)
+    }
+
+    fn touch_move(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.process(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.process(contact, transport).is_ok()
+    }
+
+    fn tick(&mut self,
+        now: Instant,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        // both instances keep time, so e.g. a pitch glide still completes while not in use,
+        // but only the instance in use is heard, so a sequencer doesn't step twice
+        let alt = self.using_alt;
+        self.instance(alt).tick(now, transport);
+        let quiet = self.quiet_sender.clone();
+        self.instance(!alt).tick(now, &quiet);
+        self.quiet_receiver.try_iter().for_each(drop);
+    }
+
+    fn osc_message(&mut self,
+        msg: &OscMessage,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let normal = self.normal.osc_message(msg, transport);
+        let alt = self.alt.osc_message(msg, transport);
+        normal || alt
+    }
+
+    fn state(&self) -> Option<Value> {
+        if self.using_alt { self.alt.state() } else { self.normal.state() }
+    }
+
+    fn set_state(&mut self,
+        state: &Value,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let alt = self.using_alt;
+        self.instance(alt).set_state(state, transport)
+    }
+
+    fn contact_mask(&self) -> contact::Mask {
+        self.normal.contact_mask() | self.alt.contact_mask()
+    }
+}
@@ -0,0 +1,298 @@
+//! Description:
+//!    Note, scale and voice helpers shared by the note producing controllers
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+
+use super::controllers::Region;
+
+/// Scale as given in the JSON IR, either a well known name or a list of
+/// semitone offsets (0..11) from the root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScaleType {
+    Named(String),
+    Intervals(Vec<u8>),
+}
+
+/// A scale rooted at a given note, represented as the set of pitch classes
+/// that belong to it
+#[derive(Debug, Clone)]
+pub struct Scale {
+    /// root note, only its pitch class matters
+    root: u8,
+    /// semitone offsets from root, sorted and within a single octave
+    steps: Vec<u8>,
+}
+
+impl Scale {
+    pub fn new(root: u8, scale: Option<ScaleType>) -> Result<Self, &'static str> {
+        let mut steps: Vec<u8> = match scale {
+            None => (0..12).collect(),
+            Some(ScaleType::Intervals(steps)) => steps.into_iter().map(|s| s % 12).collect(),
+            Some(ScaleType::Named(name)) => {
+                match name.as_str() {
+                    "chromatic" => (0..12).collect(),
+                    "major" | "ionian" => vec![0, 2, 4, 5, 7, 9, 11],
+                    "minor" | "aeolian" => vec![0, 2, 3, 5, 7, 8, 10],
+                    "harmonic_minor" => vec![0, 2, 3, 5, 7, 8, 11],
+                    "melodic_minor" => vec![0, 2, 3, 5, 7, 9, 11],
+                    "dorian" => vec![0, 2, 3, 5, 7, 9, 10],
+                    "phrygian" => vec![0, 1, 3, 5, 7, 8, 10],
+                    "lydian" => vec![0, 2, 4, 6, 7, 9, 11],
+                    "mixolydian" => vec![0, 2, 4, 5, 7, 9, 10],
+                    "locrian" => vec![0, 1, 3, 5, 6, 8, 10],
+                    "major_pentatonic" => vec![0, 2, 4, 7, 9],
+                    "minor_pentatonic" => vec![0, 3, 5, 7, 10],
+                    "blues" => vec![0, 3, 5, 6, 7, 10],
+                    "whole_tone" => vec![0, 2, 4, 6, 8, 10],
+                    _ => return Err("unknown scale name"),
+                }
+            }
+        };
+
+        steps.sort();
+        steps.dedup();
+
+        if steps.len() == 0 {
+            return Err("scale must contain at least one note");
+        }
+
+        Ok(Scale {
+            root: root % 12,
+            steps: steps,
+        })
+    }
+
+    /// is note a member of the scale
+    pub fn contains(&self, note: u8) -> bool {
+        let pc = (note as i32 - self.root as i32).rem_euclid(12) as u8;
+        self.steps.contains(&pc)
+    }
+
+    /// all notes of the scale in the inclusive range low..high
+    pub fn notes(&self, low: u8, high: u8) -> Vec<u8> {
+        (low..=high).filter(|n| self.contains(*n)).collect()
+    }
+
+    /// scale note closest to a continuous pitch
+    pub fn nearest(&self, pitch: f32) -> f32 {
+        let base = pitch.floor() as i32;
+        // a scale has at least one note per octave, so one will be found within 6 semitones
+        (base - 6..=base + 7)
+            .filter(|n| self.steps.contains(&((n - self.root as i32).rem_euclid(12) as u8)))
+            .map(|n| n as f32)
+            .min_by(|a, b| (a - pitch).abs().partial_cmp(&(b - pitch).abs()).unwrap_or(cmp::Ordering::Equal))
+            .unwrap_or(pitch)
+    }
+}
+
+/// Strength of snapping toward scale notes as given in the JSON IR, either
+/// none, soft or hard, or a strength between 0 (none) and 1 (hard)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RoundingType {
+    Named(String),
+    Strength(f32),
+}
+
+/// strength, 0..1, of rounding
+pub fn rounding_strength(rounding: Option<RoundingType>) -> Result<f32, &'static str> {
+    match rounding {
+        None => Ok(0.0),
+        Some(RoundingType::Strength(s)) => Ok(num::clamp(s, 0.0, 1.0)),
+        Some(RoundingType::Named(name)) => {
+            match name.as_str() {
+                "none" => Ok(0.0),
+                "soft" => Ok(0.6),
+                "hard" => Ok(1.0),
+                _ => Err("unknown rounding, expected none, soft or hard"),
+            }
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Allocates a channel to each sounding contact, MPE style, so that per note
+/// expression can be sent on its own channel. Channels are handed out least
+/// recently used first, giving release tails the best chance to finish.
+#[derive(Debug, Clone)]
+pub struct VoiceAllocator {
+    free: VecDeque<u8>,
+    voices: HashMap<u8, u8>,
+}
+
+impl VoiceAllocator {
+    /// create allocator for channels first..first+count-1
+    pub fn new(first: u8, count: u8) -> Self {
+        VoiceAllocator {
+            free: (first..first.saturating_add(count)).collect(),
+            voices: HashMap::new(),
+        }
+    }
+
+    /// create allocator for the member channels, 1 to 15 (default 15), of an MPE
+    /// lower zone, whose master channel is channel 1
+    pub fn lower_zone(channels: Option<u8>) -> Result<Self, &'static str> {
+        match channels.unwrap_or(15) {
+            channels @ 1..=15 => Ok(VoiceAllocator::new(2, channels)),
+            _ => Err("expected 1 to 15 channels"),
+        }
+    }
+
+    /// allocate a channel for contact, returns None if all channels are in use
+    pub fn allocate(&mut self, contact_id: u8) -> Option<u8> {
+        if let Some(channel) = self.voices.get(&contact_id) {
+            return Some(*channel);
+        }
+
+        self.free.pop_front().map(|channel| {
+            self.voices.insert(contact_id, channel);
+            channel
+        })
+    }
+
+    /// channel currently allocated to contact
+    pub fn channel(&self, contact_id: u8) -> Option<u8> {
+        self.voices.get(&contact_id).cloned()
+    }
+
+    /// release contact's channel, returning the channel it held
+    pub fn release(&mut self, contact_id: u8) -> Option<u8> {
+        self.voices.remove(&contact_id).map(|channel| {
+            self.free.push_back(channel);
+            channel
+        })
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Shapes a normalised (0..1) strike strength before it is turned into a velocity
+#[derive(Debug, Clone, Copy)]
+pub enum VelocityCurve {
+    Linear,
+    /// more velocity for lighter hits
+    Soft,
+    /// less velocity for lighter hits
+    Hard,
+    /// compressed at both extremes
+    SCurve,
+}
+
+impl VelocityCurve {
+    pub fn new(curve: Option<String>) -> Result<Self, &'static str> {
+        match curve.as_ref().map(|c| c.as_str()) {
+            None | Some("linear") => Ok(VelocityCurve::Linear),
+            Some("soft") => Ok(VelocityCurve::Soft),
+            Some("hard") => Ok(VelocityCurve::Hard),
+            Some("s_curve") => Ok(VelocityCurve::SCurve),
+            _ => Err("unknown velocity curve"),
+        }
+    }
+
+    /// velocity, 1..127, for strength in the range 0..1
+    pub fn velocity(&self, strength: f32) -> i32 {
+        let s = num::clamp(strength, 0.0, 1.0);
+        let shaped = match *self {
+            VelocityCurve::Linear => s,
+            VelocityCurve::Soft => s.sqrt(),
+            VelocityCurve::Hard => s * s,
+            VelocityCurve::SCurve => s * s * (3.0 - 2.0 * s),
+        };
+        cmp::max(1, (shaped * 127.0).round() as i32)
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Isomorphic note grid, where moving by a cell in a given direction always
+/// changes pitch by the same interval. Rows run from the bottom of a region
+/// upwards, and odd rows can be offset to the right by a fraction of a cell
+/// to give a hexagonal (brick) layout, in which case row_interval is the
+/// interval to the cell up and to the right
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Isomorphic {
+    /// note of bottom left cell
+    pub root: u8,
+    /// interval to the next cell on the right
+    pub col_interval: i32,
+    /// interval to the cell above (up and to the right when offset)
+    pub row_interval: i32,
+    /// offset of odd rows, as a fraction of cell width
+    pub row_offset: f32,
+    pub cell_width: f32,
+    pub cell_height: f32,
+}
+
+impl Isomorphic {
+    /// column interval, row interval and row offset of well known layouts
+    pub fn preset(name: &str) -> Option<(i32, i32, f32)> {
+        match name {
+            "wicki_hayden" => Some((2, 7, 0.5)),
+            "harmonic_table" => Some((1, 4, 0.5)),
+            "fourths" => Some((1, 5, 0.0)),
+            _ => None,
+        }
+    }
+
+    /// number of whole columns that fit in region
+    pub fn columns(&self, region: &Region) -> usize {
+        let width = region.width - self.row_offset * self.cell_width;
+        if self.cell_width <= 0.0 || width <= 0.0 {
+            return 0;
+        }
+        (width / self.cell_width) as usize
+    }
+
+    /// number of whole rows that fit in region
+    pub fn rows(&self, region: &Region) -> usize {
+        if self.cell_height <= 0.0 || region.height <= 0.0 {
+            return 0;
+        }
+        (region.height / self.cell_height) as usize
+    }
+
+    /// area covered by cell
+    pub fn cell_region(&self, region: &Region, col: usize, row: usize) -> Region {
+        let shift = (row % 2) as f32 * self.row_offset * self.cell_width;
+        Region {
+            x: region.x + shift + col as f32 * self.cell_width,
+            y: region.y + region.height - (row + 1) as f32 * self.cell_height,
+            width: self.cell_width,
+            height: self.cell_height,
+        }
+    }
+
+    /// cell containing position, None if position falls outside the grid
+    pub fn cell(&self, region: &Region, x: f32, y: f32) -> Option<(usize, usize)> {
+        if self.cell_width <= 0.0 || self.cell_height <= 0.0 {
+            return None;
+        }
+
+        let row = ((region.y + region.height - y) / self.cell_height).floor();
+        if row < 0.0 || row as usize >= self.rows(region) {
+            return None;
+        }
+        let row = row as usize;
+
+        let shift = (row % 2) as f32 * self.row_offset * self.cell_width;
+        let col = ((x - region.x - shift) / self.cell_width).floor();
+        if col < 0.0 || col as usize >= self.columns(region) {
+            return None;
+        }
+
+        Some((col as usize, row))
+    }
+
+    /// note of cell, which might fall outside of the MIDI range
+    pub fn note(&self, col: usize, row: usize) -> i32 {
+        // columns are skewed, so moving up and to the right keeps the same column
+        let col = if self.row_offset > 0.0 { col as i32 - (row / 2) as i32 } else { col as i32 };
+        self.root as i32 + col * self.col_interval + row as i32 * self.row_interval
+    }
+}
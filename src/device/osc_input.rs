@@ -0,0 +1,44 @@
+//! Description:
+//!    OSC input, packets sent to us by the host, e.g. a DAW reporting
+//!    parameter changes, for passing on to controllers
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::net::{UdpSocket, SocketAddrV4};
+use std::sync::mpsc::{Receiver, channel};
+use std::thread;
+
+use rosc::{OscPacket, decoder};
+
+/// Listen for OSC packets on addr, in a separate thread. Packets received are
+/// delivered on the returned channel, which can be given to an interface with
+/// set_osc_input. The thread exits once the channel's receiver is dropped
+pub fn listen(addr: SocketAddrV4) -> Result<Receiver<OscPacket>, &'static str> {
+    let socket = UdpSocket::bind(addr).map_err(|_| "failed to bind OSC input socket")?;
+    let (sender, receiver) = channel();
+
+    thread::spawn(move || {
+        let mut buf = [0u8; decoder::MTU];
+        loop {
+            match socket.recv_from(&mut buf) {
+                Ok((size, from)) => {
+                    match decoder::decode(&buf[..size]) {
+                        Ok(packet) => {
+                            if sender.send(packet).is_err() {
+                                break;
+                            }
+                        },
+                        Err(e) => warn!("invalid OSC packet from {}: {:?}", from, e),
+                    }
+                },
+                Err(e) => {
+                    error!("OSC input failed: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(receiver)
+}
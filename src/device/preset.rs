@@ -0,0 +1,308 @@
+//! Description:
+//!    Snapshots of controller state, kept as named presets that are saved to
+//!    a JSON file, recalled over OSC or by a preset controller, and morphed
+//!    between over time
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::net::{SocketAddrV4};
+use std::sync::mpsc::{Sender};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use rosc::{OscPacket, OscMessage, OscType};
+
+use serde_json::{Map, Value};
+
+use crate::sensel::*;
+
+use super::controllers::*;
+
+/// Presets section of the JSON IR
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PresetsConfig {
+    /// OSC address presets are saved and recalled on (default /preset)
+    pub address: Option<String>,
+    /// file presets are loaded from and saved to
+    pub file: Option<String>,
+    /// preset recalled when the interface starts
+    pub initial: Option<String>,
+}
+
+/// snapshot of the state of every controller that has state, keyed by controller ID
+pub fn snapshot(controls: &Vec<Box<Controller>>) -> Value {
+    let mut snapshot = Map::new();
+    for (i, control) in controls.iter().enumerate() {
+        if let Some(state) = control.state() {
+            snapshot.insert((i + 1).to_string(), state);
+        }
+    }
+    Value::Object(snapshot)
+}
+
+/// restore controllers from snapshot, any not in the snapshot are left unchanged
+pub fn restore(
+    controls: &mut Vec<Box<Controller>>,
+    snapshot: &Value,
+    transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+    if let Some(snapshot) = snapshot.as_object() {
+        for (id, state) in snapshot {
+            match id.parse::<usize>() {
+                Ok(id) if id >= 1 && id <= controls.len() => {
+                    if !controls[id - 1].set_state(state, transport) {
+                        warn!("unexpected state for controller {}", id);
+                    }
+                },
+                _ => warn!("snapshot has unknown controller {}", id),
+            }
+        }
+    }
+}
+
+/// state part way, 0..1, between from and to. Numbers are interpolated, while anything
+/// else, e.g. a toggle, switches from one to the other at the end
+pub fn interpolate(from: &Value, to: &Value, t: f32) -> Value {
+    match (from, to) {
+        (Value::Number(a), Value::Number(b)) => {
+            let a = a.as_f64().unwrap_or(0.0);
+            let b = b.as_f64().unwrap_or(0.0);
+            Value::from(a + (b - a) * t as f64)
+        },
+        (Value::Array(a), Value::Array(b)) if a.len() == b.len() => {
+            Value::Array(a.iter().zip(b).map(|(a, b)| interpolate(a, b, t)).collect())
+        },
+        (Value::Object(a), Value::Object(b)) => {
+            Value::Object(b.iter().map(|(k, b)|
+                (k.clone(), a.get(k).map_or(b.clone(), |a| interpolate(a, b, t)))).collect())
+        },
+        _ => if t < 1.0 { from.clone() } else { to.clone() },
+    }
+}
+
+/// Change to presets, applied by the interface on its next update
+#[derive(Debug, Clone)]
+enum Request {
+    Save(String),
+    Recall(String),
+    Morph(String, Duration),
+}
+
+#[derive(Debug, Clone)]
+struct Morph {
+    from: Value,
+    to: Value,
+    start: Instant,
+    duration: Duration,
+}
+
+/// Named presets of a layout
+#[derive(Debug)]
+pub struct Presets {
+    address: String,
+    file: Option<String>,
+    presets: BTreeMap<String, Value>,
+    requests: Vec<Request>,
+    morph: Option<Morph>,
+}
+
+/// Presets, shared between the interface and any preset controllers
+pub type SharedPresets = Rc<RefCell<Presets>>;
+
+impl Presets {
+    pub fn new(config: PresetsConfig) -> Result<Self, &'static str> {
+        let presets = match &config.file {
+            Some(file) => match fs::read_to_string(file) {
+                Ok(contents) => serde_json::from_str(&contents)
+                                    .map_err(|_| "unexpected format error with presets file")?,
+                // created on first save
+                Err(_) => BTreeMap::new(),
+            },
+            None => BTreeMap::new(),
+        };
+
+        Ok(Presets {
+            address: config.address.unwrap_or("/preset".to_string()),
+            file: config.file,
+            presets: presets,
+            requests: config.initial.into_iter().map(|name| Request::Recall(name)).collect(),
+            morph: None,
+        })
+    }
+
+    /// store current state as preset name
+    pub fn save(&mut self, name: String) {
+        self.requests.push(Request::Save(name));
+    }
+
+    /// restore preset name
+    pub fn recall(&mut self, name: String) {
+        self.requests.push(Request::Recall(name));
+    }
+
+    /// move from current state to preset name over duration
+    pub fn morph(&mut self, name: String, duration: Duration) {
+        self.requests.push(Request::Morph(name, duration));
+    }
+
+    /// handle address/save name, address/recall name, and address/morph name ms, returns
+    /// true if msg was for presets
+    pub fn osc_message(&mut self, msg: &OscMessage) -> bool {
+        if !msg.addr.starts_with(&self.address) {
+            return false;
+        }
+
+        let args = msg.args.as_ref();
+        let name = match args.and_then(|args| args.first()) {
+            Some(OscType::String(name)) => name.clone(),
+            _ => return false,
+        };
+
+        match &msg.addr[self.address.len()..] {
+            "/save" => self.save(name),
+            "/recall" => self.recall(name),
+            "/morph" => {
+                let ms = args.and_then(|args| args.get(1)).and_then(osc_f32).unwrap_or(0.0);
+                self.morph(name, Duration::from_millis(ms.max(0.0) as u64));
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    fn write(&self) {
+        if let Some(file) = &self.file {
+            let result = serde_json::to_string_pretty(&self.presets)
+                .map_err(|e| e.to_string())
+                .and_then(|contents| fs::write(file, contents).map_err(|e| e.to_string()));
+            if let Err(e) = result {
+                error!("failed to save presets to {}: {}", file, e);
+            }
+        }
+    }
+
+    /// apply any requests, and morph in progress, to controls
+    pub fn update(
+        &mut self,
+        now: Instant,
+        controls: &mut Vec<Box<Controller>>,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        for request in self.requests.drain(..).collect::<Vec<_>>() {
+            match request {
+                Request::Save(name) => {
+                    self.presets.insert(name, snapshot(controls));
+                    self.write();
+                },
+                Request::Recall(name) => {
+                    match self.presets.get(&name) {
+                        Some(preset) => {
+                            // a recall cancels any morph in progress
+                            self.morph = None;
+                            restore(controls, preset, transport);
+                        },
+                        None => warn!("unknown preset {}", name),
+                    }
+                },
+                Request::Morph(name, duration) => {
+                    match self.presets.get(&name) {
+                        Some(preset) => {
+                            self.morph = Some(Morph {
+                                from: snapshot(controls),
+                                to: preset.clone(),
+                                start: now,
+                                duration: duration,
+                            });
+                        },
+                        None => warn!("unknown preset {}", name),
+                    }
+                },
+            }
+        }
+
+        if let Some(morph) = self.morph.take() {
+            let elapsed = now.duration_since(morph.start);
+            let t = if elapsed >= morph.duration {
+                1.0
+            }
+            else {
+                elapsed.as_millis() as f32 / morph.duration.as_millis() as f32
+            };
+
+            restore(controls, &interpolate(&morph.from, &morph.to, t), transport);
+            if t < 1.0 {
+                self.morph = Some(morph);
+            }
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Preset controller
+///  Pressing recalls a named preset, morphing to it if a morph time is given, or,
+/// when saving, stores the current state of the surface as the preset
+#[derive(Debug, Clone)]
+pub struct Preset {
+    presets: SharedPresets,
+    /// name of preset
+    name: String,
+    /// time to morph to preset, recalled immediately if None
+    morph: Option<Duration>,
+    /// save, rather than recall, preset
+    save: bool,
+}
+
+impl Preset {
+    pub fn new(
+        presets: SharedPresets, name: Option<String>,
+        morph_ms: Option<u64>, save: Option<bool>) -> Result<Self, &'static str> {
+        Ok(Preset {
+            presets: presets,
+            name: name.ok_or("preset controller must name its preset")?,
+            morph: morph_ms.map(Duration::from_millis),
+            save: save.unwrap_or(false),
+        })
+    }
+}
+
+impl Controller for Preset {
+    fn name(&self) -> &'static str {
+        "preset"
+    }
+
+    fn touch_start(&mut self,
+             contact: &contact::TrackedContact,
+             _transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        if contact.state == contact::State::CONTACT_START {
+            let mut presets = self.presets.borrow_mut();
+            if self.save {
+                presets.save(self.name.clone());
+            }
+            else {
+                match self.morph {
+                    Some(duration) => presets.morph(self.name.clone(), duration),
+                    None => presets.recall(self.name.clone()),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn touch_move(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+}
@@ -0,0 +1,549 @@
+//! Description:
+//!    Registry of controller factories, keyed by type_id, used to build the
+//!    controllers of a layout. Applications can register factories of their own
+//!    to add controller types without changing the crate
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use serde_json::{Map, Value};
+
+use super::controllers::*;
+use super::music::{ScaleType, Isomorphic, RoundingType};
+use super::template::TemplateArg;
+use super::gate::{Gate, Thresholds};
+use super::route::{Route, Destinations, DestinationRef};
+use super::preset::{Preset, Presets, PresetsConfig, SharedPresets};
+use super::modifier::{Modifier, Modified, Modifiers};
+use super::smoothing::{Smoothed, Smoother, SmoothingConfig};
+
+// constants
+
+const TYPE_PAD : &'static str = "pad";
+const TYPE_DPAD : &'static str = "dpad";
+const TYPE_HSLIDER : &'static str = "horz_slider";
+const TYPE_VSLIDER : &'static str = "vert_slider"; 
+const TYPE_ENDLESS : &'static str = "endless";
+const TYPE_KEYBOARD : &'static str = "keyboard";
+const TYPE_DRUM : &'static str = "drum";
+const TYPE_SEQUENCER : &'static str = "sequencer";
+const TYPE_PITCH_SURFACE : &'static str = "pitch_surface";
+const TYPE_KNOB : &'static str = "knob";
+const TYPE_CROSSFADER : &'static str = "crossfader";
+const TYPE_FADER_BANK : &'static str = "fader_bank";
+const TYPE_STRINGS : &'static str = "strings";
+const TYPE_MACRO : &'static str = "macro";
+const TYPE_CHORD : &'static str = "chord";
+const TYPE_TOGGLE : &'static str = "toggle";
+const TYPE_PRESET : &'static str = "preset";
+const TYPE_MODIFIER : &'static str = "modifier";
+const TYPE_JOYSTICK : &'static str = "joystick";
+const TYPE_ENVELOPE : &'static str = "envelope";
+const TYPE_GESTURE : &'static str = "gesture";
+#[cfg(feature = "scripting")]
+const TYPE_SCRIPT : &'static str = "script";
+
+//-----------------------------------------------------------------------------
+
+/// Layout information and shared state available to controller factories
+pub struct BuildContext<'a> {
+    /// ID of controller being built
+    pub id: u32,
+    /// layout buffer, giving the ID of the controller covering each cell
+    pub buffer: &'a Vec<Vec<u32>>,
+    /// area of Morph covered by controller
+    pub region: Region,
+    pub chokes: ChokeGroups,
+    pub presets: SharedPresets,
+    pub modifiers: Modifiers,
+}
+
+/// Builds a controller from its JSON IR object
+pub type Factory = Box<Fn(&Value, &BuildContext) -> Result<Box<Controller>, &'static str>>;
+
+/// Controller factories, keyed by type_id
+pub struct Registry {
+    factories: HashMap<String, Factory>,
+    /// thresholds of each type, used when neither controller nor layout give them
+    thresholds: HashMap<String, Thresholds>,
+}
+
+impl Registry {
+    /// registry of the built in controller types
+    pub fn new() -> Self {
+        let mut registry = Registry {
+            factories: HashMap::new(),
+            thresholds: HashMap::new(),
+        };
+        register_builtins(&mut registry);
+        registry
+    }
+
+    /// register factory for type_id, replacing any existing factory for it
+    pub fn register<F>(&mut self, type_id: &str, factory: F)
+        where F: Fn(&Value, &BuildContext) -> Result<Box<Controller>, &'static str> + 'static {
+        self.factories.insert(type_id.to_string(), Box::new(factory));
+    }
+
+    /// set default force thresholds and retrigger interval for type_id
+    pub fn set_thresholds(&mut self, type_id: &str, thresholds: Thresholds) {
+        self.thresholds.insert(type_id.to_string(), thresholds);
+    }
+
+    /// true if a factory is registered for type_id
+    pub fn contains(&self, type_id: &str) -> bool {
+        self.factories.contains_key(type_id)
+    }
+
+    /// build controller of type_id from its JSON IR object
+    pub fn build(
+        &self, 
+        type_id: &str, 
+        json: &Value, 
+        context: &BuildContext) -> Result<Box<Controller>, &'static str> {
+        match self.factories.get(type_id) {
+            Some(factory) => factory(json, context),
+            None => Err("unknown controller type"),
+        }
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::new()
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Fields of the JSON IR handled alike for every controller type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Common {
+    pub id: usize,
+    pub type_id: String,
+    pub min_force: Option<f32>,
+    pub release_force: Option<f32>,
+    pub retrigger_ms: Option<u64>,
+    pub destination: Option<DestinationRef>,
+    pub alt: Option<Map<String, Value>>,
+    pub smoothing: Option<SmoothingConfig>,
+}
+
+/// build controllers of layout, ordered on ID, along with the presets they share
+pub fn build_controls(
+    obj: &Map<String, Value>, 
+    buffer: &Vec<Vec<u32>>, 
+    registry: &Registry) -> Result<(Vec<Box<Controller>>, SharedPresets), &'static str> {
+    let controllers = match obj.get("controllers") {
+        Some(Value::Array(controllers)) => controllers,
+        _ => return Err("failed to find controllers array"),
+    };
+
+    // create an ordered (on ID) list of controls, each with any alternate, given by the
+    // modifier that engages it and the raw IR with overrides applied
+    let mut cs: Vec<(Common, Value, Option<(String, Value)>)> = vec![];
+    for c in controllers {
+        let common: Common = serde_json::from_value(c.clone())
+            .map_err(|_| "unexpected format error with controller")?;
+
+        // alternate behaviour while a modifier is engaged, given as overrides of the 
+        // controller's fields, is built as a controller of its own
+        let alternate = match (&common.alt, c) {
+            (Some(alt), Value::Object(fields)) => {
+                let modifier = match alt.get("modifier") {
+                    Some(Value::String(modifier)) => modifier.clone(),
+                    _ => return Err("alt must name its modifier"),
+                };
+                let mut fields = fields.clone();
+                fields.remove("alt");
+                for (k, v) in alt {
+                    if k != "modifier" {
+                        fields.insert(k.clone(), v.clone());
+                    }
+                }
+                Some((modifier, Value::Object(fields)))
+            },
+            _ => None,
+        };
+        cs.push((common, c.clone(), alternate));
+    }
+    cs.sort_by(|a, b| a.0.id.cmp(&b.0.id));
+
+    // controllers are found by ID, so IDs must run from 1 without duplicates or gaps
+    for (index, (common, _, _)) in cs.iter().enumerate() {
+        if index > 0 && common.id == cs[index - 1].0.id {
+            return Err("duplicate controller id");
+        }
+        if common.id != index + 1 {
+            return Err("controller ids must run from 1 without gaps");
+        }
+    }
+
+    // Each controller has a unique ID, between 0..number_of_controllers-1, which is 
+    // used as a direct index into array of Controller instances
+    let mut controls: Vec<Box<Controller>> = vec![];
+    let chokes = ChokeGroups::default();
+    let modifiers = Modifiers::default();
+
+    // layout wide thresholds, overriding those of each controller type
+    let defaults: Thresholds = match obj.get("defaults") {
+        Some(defaults) => serde_json::from_value(defaults.clone())
+                            .map_err(|_| "unexpected format error with defaults")?,
+        None => Thresholds::default(),
+    };
+
+    // named output destinations, and the layout wide destination used by
+    // controllers that don't give their own
+    let destinations = Destinations::new(obj.get("destinations"))?;
+    let destination: Option<DestinationRef> = match obj.get("destination") {
+        Some(destination) => Some(serde_json::from_value(destination.clone())
+                            .map_err(|_| "unexpected format error with destination")?),
+        None => None,
+    };
+
+    // named snapshots of controller state
+    let config: PresetsConfig = match obj.get("presets") {
+        Some(config) => serde_json::from_value(config.clone())
+                            .map_err(|_| "unexpected format error with presets")?,
+        None => PresetsConfig::default(),
+    };
+    let presets = Rc::new(RefCell::new(Presets::new(config)?));
+
+    for (common, json, alternate) in cs {
+        let context = BuildContext {
+            id: common.id as u32,
+            buffer: buffer,
+            region: Region::from_buffer(buffer, common.id as u32),
+            chokes: chokes.clone(),
+            presets: presets.clone(),
+            modifiers: modifiers.clone(),
+        };
+
+        let mut control = build_control(
+            registry, &common, &json, &context, &defaults, &destinations, &destination)?;
+        if let Some((modifier, json)) = alternate {
+            let common: Common = serde_json::from_value(json.clone())
+                .map_err(|_| "unexpected format error with controller alt")?;
+            let alt = build_control(
+                registry, &common, &json, &context, &defaults, &destinations, &destination)?;
+            control = Box::new(Modified::new(control, alt, modifier, modifiers.clone()));
+        }
+        info!("{} = {}", common.type_id, common.id);
+        controls.push(control);
+    }
+
+    Ok((controls, presets))
+}
+
+/// build a single controller, wrapped to apply its thresholds, smoothing and destinations
+fn build_control(
+    registry: &Registry,
+    common: &Common,
+    json: &Value,
+    context: &BuildContext,
+    defaults: &Thresholds,
+    destinations: &Destinations,
+    destination: &Option<DestinationRef>) -> Result<Box<Controller>, &'static str> {
+    let control = registry.build(&common.type_id, json, context)?;
+
+    // thresholds apply alike to every controller type
+    let thresholds = Thresholds {
+        min_force: common.min_force,
+        release_force: common.release_force,
+        retrigger_ms: common.retrigger_ms,
+    }.or(*defaults).or(registry.thresholds.get(&common.type_id).cloned().unwrap_or_default());
+    let control: Box<Controller> = Box::new(Gate::new(control, thresholds));
+
+    // smoothing comes before the gate, so thresholds see smoothed force, any layout
+    // wide smoothing has already been applied to every contact by the interface
+    let control: Box<Controller> = match common.smoothing.as_ref() {
+        Some(config) => match Smoother::new(config)? {
+            Some(smoother) => Box::new(Smoothed::new(control, smoother)),
+            None => control,
+        },
+        None => control,
+    };
+
+    let targets = match common.destination.as_ref().or(destination.as_ref()) {
+        Some(destination) => destinations.lookup(destination)?,
+        None => vec![],
+    };
+    if targets.is_empty() {
+        Ok(control)
+    }
+    else {
+        Ok(Box::new(Route::new(control, targets)))
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// IR fields of the built in controller types
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Control {
+    pub address: String,
+    pub args: Vec<ArgType>,
+    pub id: usize,
+    pub rgb:  Option<String>,
+    pub pressure: Option<bool>,
+    pub generate_move: Option<bool>,
+    pub generate_end: Option<bool>,
+    pub generate_coords: Option<bool>,
+    pub on: Option<ArgType>,
+    pub off: Option<ArgType>,
+    pub type_id: String,
+    pub min: Option<ArgType>,
+    pub max: Option<ArgType>,
+    pub initial: Option<ArgType>, 
+    pub incr: Option<ArgType>,
+    pub low_note: Option<u8>,
+    pub high_note: Option<u8>,
+    pub root: Option<u8>,
+    pub scale: Option<ScaleType>,
+    pub grid: Option<Isomorphic>,
+    pub channels: Option<u8>,
+    pub bend_range: Option<f32>,
+    pub max_force: Option<f32>,
+    pub window: Option<u32>,
+    pub curve: Option<String>,
+    pub choke_group: Option<u32>,
+    pub columns: Option<usize>,
+    pub rows: Option<usize>,
+    pub tempo: Option<f32>,
+    pub steps_per_beat: Option<u32>,
+    pub rounding: Option<RoundingType>,
+    pub glide_ms: Option<u64>,
+    pub gesture: Option<String>,
+    pub fine: Option<f32>,
+    pub fine_force: Option<f32>,
+    pub double_tap_ms: Option<u64>,
+    pub law: Option<String>,
+    pub count: Option<usize>,
+    pub orientation: Option<String>,
+    pub addressing: Option<String>,
+    pub names: Option<Vec<String>>,
+    pub notes: Option<Vec<u8>>,
+    pub max_speed: Option<f32>,
+    pub damp_width: Option<f32>,
+    pub damp_speed: Option<f32>,
+    pub targets: Option<Vec<MacroTarget>>,
+    pub template: Option<Vec<TemplateArg>>,
+    pub feedback: Option<String>,
+    pub preset: Option<String>,
+    pub morph_ms: Option<u64>,
+    pub save: Option<bool>,
+    pub modifier: Option<String>,
+    pub latch: Option<bool>,
+    pub inertia: Option<bool>,
+    pub friction: Option<f32>,
+    pub mode: Option<String>,
+    pub radius: Option<f32>,
+    pub gate: Option<String>,
+    pub return_ms: Option<u64>,
+    pub return_curve: Option<String>,
+    pub max_area: Option<f32>,
+    pub max_twist: Option<f32>,
+}
+
+/// parse IR of a built in controller type
+fn control(json: &Value) -> Result<Control, &'static str> {
+    serde_json::from_value(json.clone()).map_err(|_| "unexpected format error with controller")
+}
+
+fn register_builtins(registry: &mut Registry) {
+    registry.register(TYPE_PAD, |json, context| {
+        let ctl = control(json)?;
+        let pressure = ctl.pressure.map_or(false, |_| true);
+        let generate_move = ctl.generate_move.map_or(false, |_| true);
+        let generate_end = ctl.generate_end.map_or(false, |_| true);
+        let generate_coords = ctl.generate_coords.map_or(false, |_| true);
+        Ok(Box::new(
+            Pad::new(
+                ctl.address, 
+                ctl.args, 
+                pressure, 
+                generate_move, 
+                generate_end,
+                generate_coords,
+                context.region,
+                ctl.template)?))
+    });
+    registry.set_thresholds(
+        TYPE_PAD, Thresholds { min_force: Some(20.0), release_force: None, retrigger_ms: Some(20) });
+
+    registry.register(TYPE_DPAD, |json, _context| {
+        let ctl = control(json)?;
+        let on = ctl.on.map_or(ArgType::IType(0), |x| x);
+        let off = ctl.off.map_or(ArgType::IType(0), |x| x);
+        Ok(Box::new(DPad::new(ctl.address, on, off, ctl.args)))
+    });
+    registry.set_thresholds(
+        TYPE_DPAD, Thresholds { min_force: None, release_force: None, retrigger_ms: Some(20) });
+
+    registry.register(TYPE_VSLIDER, |json, _context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            VSlider::new(
+                ctl.address, ctl.args, ctl.min, ctl.max, ctl.initial, ctl.incr,
+                ctl.feedback, ctl.inertia, ctl.friction)))
+    });
+
+    registry.register(TYPE_HSLIDER, |json, _context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            HSlider::new(
+                ctl.address, ctl.args, ctl.min, ctl.max, ctl.initial, ctl.incr,
+                ctl.feedback, ctl.inertia, ctl.friction)))
+    });
+
+    registry.register(TYPE_ENDLESS, |json, _context| {
+        let ctl = control(json)?;
+        Ok(Box::new(Endless::new(ctl.address, ctl.args)))
+    });
+
+    registry.register(TYPE_KEYBOARD, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            Keyboard::new(
+                ctl.address, ctl.args, context.region,
+                ctl.low_note, ctl.high_note, ctl.root, ctl.scale, ctl.grid,
+                ctl.channels, ctl.bend_range, ctl.max_force)?))
+    });
+
+    registry.register(TYPE_DRUM, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            Drum::new(
+                ctl.address, ctl.args,
+                ctl.window, ctl.max_force, ctl.curve,
+                ctl.choke_group, context.chokes.clone())?))
+    });
+
+    registry.register(TYPE_SEQUENCER, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            Sequencer::new(
+                ctl.address, ctl.args, context.region,
+                ctl.columns, ctl.rows,
+                ctl.tempo, ctl.steps_per_beat, ctl.max_force)))
+    });
+
+    registry.register(TYPE_PITCH_SURFACE, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            PitchSurface::new(
+                ctl.address, ctl.args, context.region,
+                ctl.low_note, ctl.high_note, ctl.root, ctl.scale,
+                ctl.rounding, ctl.glide_ms, ctl.channels, ctl.max_force)?))
+    });
+
+    registry.register(TYPE_KNOB, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            Knob::new(
+                ctl.address, ctl.args, context.region, ctl.gesture,
+                ctl.min, ctl.max, ctl.initial, ctl.incr,
+                ctl.fine, ctl.fine_force, ctl.double_tap_ms,
+                ctl.feedback, ctl.inertia, ctl.friction)?))
+    });
+
+    registry.register(TYPE_CROSSFADER, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            Crossfader::new(
+                ctl.address, ctl.args, context.region, ctl.law, ctl.initial, ctl.feedback)?))
+    });
+
+    registry.register(TYPE_FADER_BANK, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            FaderBank::new(
+                ctl.address, ctl.args, context.region,
+                ctl.count, ctl.orientation, ctl.addressing, ctl.names,
+                ctl.min, ctl.max, ctl.initial, ctl.feedback)?))
+    });
+
+    registry.register(TYPE_STRINGS, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            Strings::new(
+                ctl.address, ctl.args, context.region, ctl.notes,
+                ctl.max_speed, ctl.max_force, ctl.curve,
+                ctl.damp_width, ctl.damp_speed)?))
+    });
+
+    registry.register(TYPE_MACRO, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(Macro::new(
+            context.region, ctl.targets, ctl.max_force, ctl.max_area, ctl.max_twist)?))
+    });
+
+    registry.register(TYPE_CHORD, |json, _context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            Chord::new(ctl.address, ctl.args, ctl.notes, ctl.max_force, ctl.curve)?))
+    });
+
+    registry.register(TYPE_TOGGLE, |json, _context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            Toggle::new(ctl.address, ctl.args, ctl.on, ctl.off, ctl.initial, ctl.feedback)))
+    });
+
+    registry.register(TYPE_PRESET, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            Preset::new(context.presets.clone(), ctl.preset, ctl.morph_ms, ctl.save)?))
+    });
+
+    registry.register(TYPE_MODIFIER, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            Modifier::new(ctl.address, ctl.args, ctl.modifier, ctl.latch, context.modifiers.clone())?))
+    });
+
+    registry.register(TYPE_JOYSTICK, |json, context| {
+        let ctl = control(json)?;
+        Ok(Box::new(
+            Joystick::new(
+                ctl.address, ctl.args, context.region,
+                ctl.mode, ctl.radius, ctl.gate, ctl.return_ms, ctl.return_curve)?))
+    });
+
+    registry.register(TYPE_ENVELOPE, super::envelope::factory);
+
+    registry.register(TYPE_GESTURE, super::gesture::factory);
+
+    #[cfg(feature = "scripting")]
+    registry.register(TYPE_SCRIPT, super::script::factory);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn build(ids: &[u32]) -> Result<usize, &'static str> {
+        let controllers: Vec<Value> = ids.iter()
+            .map(|id| json!({ "type_id": "pad", "id": id, "address": "/pad", "args": [] }))
+            .collect();
+        let obj = match json!({ "controllers": controllers }) {
+            Value::Object(obj) => obj,
+            _ => unreachable!(),
+        };
+        let buffer = vec![vec![0; 4]; 4];
+        build_controls(&obj, &buffer, &Registry::new()).map(|(controls, _)| controls.len())
+    }
+
+    #[test]
+    fn controller_ids_run_from_one() {
+        assert_eq!(build(&[2, 1, 3]), Ok(3));
+        assert!(build(&[1, 1, 2]).is_err());
+        assert!(build(&[1, 3]).is_err());
+        assert!(build(&[0, 1]).is_err());
+    }
+}
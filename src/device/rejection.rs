@@ -0,0 +1,221 @@
+//! Description:
+//!    Palm and accidental touch rejection, rules applied to each contact
+//!    before it reaches any controller
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::collections::HashSet;
+
+use crate::sensel::*;
+
+use super::controllers::Region;
+
+/// Rejection section of the JSON IR, any rule not given is not applied
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RejectionConfig {
+    /// largest area of an accepted contact
+    pub max_area: Option<f32>,
+    /// longest major axis of an accepted contact's ellipse
+    pub max_major_axis: Option<f32>,
+    /// longest minor axis of an accepted contact's ellipse
+    pub max_minor_axis: Option<f32>,
+    /// lowest force per unit area of an accepted contact, a resting palm is broad
+    /// but light
+    pub min_force_per_area: Option<f32>,
+    /// reject contacts that start outside of any controller (default false)
+    pub reject_outside: Option<bool>,
+    /// areas of the surface where contacts that start are rejected, e.g. where the
+    /// heel of the hand rests
+    #[serde(default)]
+    pub zones: Vec<Region>,
+}
+
+/// What to do with a contact
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Verdict {
+    /// pass on to controllers
+    Accept,
+    /// drop, the contact has never reached a controller
+    Reject,
+    /// contact was accepted, but now breaks a rule, so should be ended at its
+    /// controller and then dropped
+    Cancel,
+}
+
+/// Applies rejection rules to contacts. Once rejected a contact stays rejected
+/// until it ends, even if it later moves over a controller or back within the rules
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    config: RejectionConfig,
+    rejected: HashSet<u8>,
+}
+
+impl Rejection {
+    pub fn new(config: RejectionConfig) -> Self {
+        Rejection {
+            config: config,
+            rejected: HashSet::new(),
+        }
+    }
+
+    /// contact data the rules need the device to report
+    pub fn contact_mask(&self) -> contact::Mask {
+        if self.config.max_major_axis.is_some() || self.config.max_minor_axis.is_some() {
+            contact::Mask::ELLIPSE
+        }
+        else {
+            contact::Mask::empty()
+        }
+    }
+
+    /// true if contact's shape or force breaks a rule
+    fn breaks_rules(&self, contact: &contact::Contact) -> bool {
+        let config = &self.config;
+        config.max_area.map_or(false, |max| contact.area > max) ||
+        config.max_major_axis.map_or(false, |max| contact.ellipse.map_or(false, |e| e.major_axis > max)) ||
+        config.max_minor_axis.map_or(false, |max| contact.ellipse.map_or(false, |e| e.minor_axis > max)) ||
+        config.min_force_per_area.map_or(false, |min|
+            contact.area > 0.0 && contact.total_force / contact.area < min)
+    }
+
+    /// true if contact starting at its position is rejected, outside is true if it
+    /// is not over any controller
+    fn rejected_start(&self, contact: &contact::Contact, outside: bool) -> bool {
+        (outside && self.config.reject_outside.unwrap_or(false)) ||
+        self.config.zones.iter().any(|zone|
+            contact.x >= zone.x && contact.x < zone.x + zone.width &&
+            contact.y >= zone.y && contact.y < zone.y + zone.height)
+    }
+
+    /// verdict for contact, where outside is true if it started outside of any controller
+    pub fn check(&mut self, contact: &contact::Contact, outside: bool) -> Verdict {
+        let verdict = match contact.state {
+            contact::State::CONTACT_START => {
+                self.rejected.remove(&contact.id);
+                if self.rejected_start(contact, outside) || self.breaks_rules(contact) {
+                    self.rejected.insert(contact.id);
+                    Verdict::Reject
+                }
+                else {
+                    Verdict::Accept
+                }
+            },
+            _ => {
+                if self.rejected.contains(&contact.id) {
+                    Verdict::Reject
+                }
+                else if self.breaks_rules(contact) {
+                    // e.g. a palm that landed lightly, then spread as it settled
+                    self.rejected.insert(contact.id);
+                    Verdict::Cancel
+                }
+                else {
+                    Verdict::Accept
+                }
+            },
+        };
+
+        if contact.state == contact::State::CONTACT_END {
+            self.rejected.remove(&contact.id);
+        }
+        verdict
+    }
+}
+
+/// rejection rules of layout, if it gives any
+pub fn layout(obj: &serde_json::Map<String, serde_json::Value>) -> Result<Option<Rejection>, &'static str> {
+    match obj.get("rejection") {
+        Some(config) => {
+            let config: RejectionConfig = serde_json::from_value(config.clone())
+                .map_err(|_| "unexpected format error with rejection")?;
+            Ok(Some(Rejection::new(config)))
+        },
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sensel::contact::{Contact, Ellipse, State};
+
+    fn check(config: RejectionConfig, contact: &Contact) -> Verdict {
+        Rejection::new(config).check(contact, false)
+    }
+
+    #[test]
+    fn max_area_rejects_large_contact() {
+        let config = RejectionConfig { max_area: Some(20.0), ..Default::default() };
+        let mut contact = Contact::at(1, State::CONTACT_START, 10.0, 10.0);
+        assert_eq!(check(config.clone(), &contact), Verdict::Accept);
+        contact.area = 30.0;
+        assert_eq!(check(config, &contact), Verdict::Reject);
+    }
+
+    #[test]
+    fn axis_limits_reject_long_ellipse() {
+        let mut contact = Contact::at(1, State::CONTACT_START, 10.0, 10.0);
+        contact.ellipse = Some(Ellipse { orientation: 0.0, major_axis: 12.0, minor_axis: 6.0 });
+
+        let major = RejectionConfig { max_major_axis: Some(10.0), ..Default::default() };
+        assert_eq!(check(major, &contact), Verdict::Reject);
+        let minor = RejectionConfig { max_minor_axis: Some(5.0), ..Default::default() };
+        assert_eq!(check(minor, &contact), Verdict::Reject);
+        let both = RejectionConfig { max_major_axis: Some(15.0), max_minor_axis: Some(8.0), ..Default::default() };
+        assert_eq!(Rejection::new(both.clone()).contact_mask(), contact::Mask::ELLIPSE);
+        assert_eq!(check(both, &contact), Verdict::Accept);
+    }
+
+    #[test]
+    fn min_force_per_area_rejects_light_broad_contact() {
+        let config = RejectionConfig { min_force_per_area: Some(5.0), ..Default::default() };
+        let mut contact = Contact::at(1, State::CONTACT_START, 10.0, 10.0);
+        assert_eq!(check(config.clone(), &contact), Verdict::Accept);
+        contact.area = 40.0;
+        assert_eq!(check(config, &contact), Verdict::Reject);
+    }
+
+    #[test]
+    fn reject_outside_rejects_contact_off_controllers() {
+        let config = RejectionConfig { reject_outside: Some(true), ..Default::default() };
+        let contact = Contact::at(1, State::CONTACT_START, 10.0, 10.0);
+        assert_eq!(Rejection::new(config.clone()).check(&contact, true), Verdict::Reject);
+        assert_eq!(Rejection::new(config).check(&contact, false), Verdict::Accept);
+        assert_eq!(Rejection::new(RejectionConfig::default()).check(&contact, true), Verdict::Accept);
+    }
+
+    #[test]
+    fn zones_reject_contact_starting_within() {
+        let zone = Region { x: 0.0, y: 0.0, width: 20.0, height: 5.0 };
+        let config = RejectionConfig { zones: vec![zone], ..Default::default() };
+        assert_eq!(check(config.clone(), &Contact::at(1, State::CONTACT_START, 10.0, 2.0)), Verdict::Reject);
+        assert_eq!(check(config, &Contact::at(1, State::CONTACT_START, 10.0, 10.0)), Verdict::Accept);
+    }
+
+    #[test]
+    fn rejected_contact_stays_rejected_over_control() {
+        let config = RejectionConfig { reject_outside: Some(true), ..Default::default() };
+        let mut rejection = Rejection::new(config);
+        assert_eq!(rejection.check(&Contact::at(1, State::CONTACT_START, 10.0, 10.0), true), Verdict::Reject);
+        assert_eq!(rejection.check(&Contact::at(1, State::CONTACT_MOVE, 50.0, 10.0), false), Verdict::Reject);
+        assert_eq!(rejection.check(&Contact::at(1, State::CONTACT_END, 50.0, 10.0), false), Verdict::Reject);
+
+        // a new contact reusing the id is judged afresh
+        assert_eq!(rejection.check(&Contact::at(1, State::CONTACT_START, 50.0, 10.0), false), Verdict::Accept);
+    }
+
+    #[test]
+    fn accepted_contact_breaking_rule_is_cancelled() {
+        let config = RejectionConfig { max_area: Some(20.0), ..Default::default() };
+        let mut rejection = Rejection::new(config);
+        assert_eq!(rejection.check(&Contact::at(1, State::CONTACT_START, 10.0, 10.0), false), Verdict::Accept);
+
+        let mut spread = Contact::at(1, State::CONTACT_MOVE, 10.0, 10.0);
+        spread.area = 30.0;
+        assert_eq!(rejection.check(&spread, false), Verdict::Cancel);
+
+        // once cancelled it is dropped, even back within the rules
+        assert_eq!(rejection.check(&Contact::at(1, State::CONTACT_MOVE, 10.0, 10.0), false), Verdict::Reject);
+    }
+}
@@ -0,0 +1,200 @@
+//! Description:
+//!    Output destinations, named in the layout, and routing of a controller's
+//!    messages to them
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::net::{SocketAddr, SocketAddrV4, ToSocketAddrs};
+use std::sync::mpsc::{Sender, Receiver, channel};
+use std::collections::HashMap;
+use std::time::Instant;
+
+use rosc::{OscPacket, OscMessage};
+
+use serde_json::Value;
+
+use crate::sensel::*;
+
+use super::controllers::*;
+
+/// A destination as given in the JSON IR, either just "host:port" or with
+/// its protocol
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DestinationType {
+    Host(String),
+    Full {
+        host: String,
+        protocol: Option<String>,
+    },
+}
+
+/// Reference to one or more named destinations, as given in the JSON IR
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DestinationRef {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// resolve host:port, where host may be a name, to an IPv4 address
+fn resolve(host: &str) -> Result<SocketAddrV4, &'static str> {
+    let addrs = host.to_socket_addrs().map_err(|_| "failed to resolve destination host")?;
+    for addr in addrs {
+        if let SocketAddr::V4(addr) = addr {
+            return Ok(addr);
+        }
+    }
+    Err("destination host has no IPv4 address")
+}
+
+/// Named destinations of a layout
+#[derive(Debug, Clone, Default)]
+pub struct Destinations {
+    named: HashMap<String, SocketAddrV4>,
+}
+
+impl Destinations {
+    /// parse destinations section of layout, if present
+    pub fn new(value: Option<&Value>) -> Result<Self, &'static str> {
+        let value = match value {
+            Some(value) => value.clone(),
+            None => return Ok(Destinations::default()),
+        };
+
+        let destinations: HashMap<String, DestinationType> = serde_json::from_value(value)
+            .map_err(|_| "unexpected format error with destinations")?;
+
+        let mut named = HashMap::new();
+        for (name, destination) in destinations {
+            let addr = match destination {
+                DestinationType::Host(host) => resolve(&host)?,
+                DestinationType::Full { host, protocol } => {
+                    // messages are handed to the host's transport, which sends OSC over UDP
+                    match protocol.as_ref().map(|p| p.as_str()) {
+                        None | Some("udp") | Some("osc.udp") => resolve(&host)?,
+                        _ => return Err("unsupported destination protocol, only udp is supported"),
+                    }
+                },
+            };
+            named.insert(name, addr);
+        }
+
+        Ok(Destinations {
+            named: named,
+        })
+    }
+
+    /// addresses of referenced destinations
+    pub fn lookup(&self, destination: &DestinationRef) -> Result<Vec<SocketAddrV4>, &'static str> {
+        let names = match destination {
+            DestinationRef::One(name) => vec![name.clone()],
+            DestinationRef::Many(names) => names.clone(),
+        };
+
+        names.iter()
+            .map(|name| self.named.get(name).cloned().ok_or("unknown destination"))
+            .collect()
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Wraps a controller, sending any messages it sends without an explicit
+/// destination to each of its targets instead
+pub struct Route {
+    inner: Box<Controller>,
+    targets: Vec<SocketAddrV4>,
+    /// controller sends to this channel, rather than directly to transport
+    sender: Sender<(OscPacket, Option<SocketAddrV4>)>,
+    receiver: Receiver<(OscPacket, Option<SocketAddrV4>)>,
+}
+
+impl Route {
+    pub fn new(inner: Box<Controller>, targets: Vec<SocketAddrV4>) -> Self {
+        let (sender, receiver) = channel();
+        Route {
+            inner: inner,
+            targets: targets,
+            sender: sender,
+            receiver: receiver,
+        }
+    }
+
+    /// forward messages sent by controller to transport, fanning out to targets
+    fn flush(&self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        for (packet, addr) in self.receiver.try_iter() {
+            match addr {
+                Some(_) => transport.send((packet, addr)).unwrap(),
+                None => {
+                    for target in &self.targets {
+                        transport.send((packet.clone(), Some(*target))).unwrap();
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Controller for Route {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn touch_start(&mut self,
+             contact: &contact::TrackedContact,
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        let result = self.inner.touch_start(contact, &self.sender);
+        self.flush(transport);
+        result
+    }
+
+    fn touch_move(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let result = self.inner.touch_move(contact, &self.sender);
+        self.flush(transport);
+        result
+    }
+
+    fn touch_end(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let result = self.inner.touch_end(contact, &self.sender);
+        self.flush(transport);
+        result
+    }
+
+    fn tick(&mut self,
+        now: Instant,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        self.inner.tick(now, &self.sender);
+        self.flush(transport);
+    }
+
+    fn osc_message(&mut self,
+        msg: &OscMessage,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let result = self.inner.osc_message(msg, &self.sender);
+        self.flush(transport);
+        result
+    }
+
+    fn state(&self) -> Option<Value> {
+        self.inner.state()
+    }
+
+    fn set_state(&mut self,
+        state: &Value,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let result = self.inner.set_state(state, &self.sender);
+        self.flush(transport);
+        result
+    }
+
+    fn contact_mask(&self) -> contact::Mask {
+        self.inner.contact_mask()
+    }
+}
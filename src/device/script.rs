@@ -0,0 +1,329 @@
+//! Description:
+//!    Script controller, whose behaviour is written in Rhai, either within
+//!    the layout or in a file it references
+//!
+//!    A script defines any of the functions start(contact), move(contact),
+//!    end(contact) and tick(), called with this bound to the controller's
+//!    state, a map that persists between calls and can be set up by init(),
+//!    either through this or by returning a map. A tick() that fails is
+//!    disabled, rather than failing every loop.
+//!    Contacts are maps of their fields, e.g. contact.x_norm, contact.force or
+//!    contact.twist.
+//!    Messages are sent with emit([args]), to the controller's address after
+//!    its static arguments, or emit_to(address, [args])
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::net::{SocketAddrV4};
+use std::sync::mpsc::{Sender};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs;
+use std::rc::Rc;
+use std::time::Instant;
+
+use rosc::{OscPacket, OscMessage, OscType};
+
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+
+use serde_json::Value;
+
+use crate::sensel::*;
+
+use super::controllers::*;
+use super::expression::{self, Twist};
+use super::registry::BuildContext;
+
+/// operations a single call may perform before it is stopped
+const MAX_OPERATIONS: u64 = 10000;
+const MAX_CALL_LEVELS: usize = 16;
+const MAX_SIZE: usize = 1024;
+
+/// IR fields of a script controller
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScriptConfig {
+    pub address: String,
+    pub args: Vec<ArgType>,
+    /// script source
+    pub script: Option<String>,
+    /// file script is read from, if not given inline
+    pub script_file: Option<String>,
+    /// operation budget of each call (default 10000)
+    pub max_operations: Option<u64>,
+}
+
+/// build script controller from its JSON IR
+pub fn factory(json: &Value, context: &BuildContext) -> Result<Box<Controller>, &'static str> {
+    let config: ScriptConfig = serde_json::from_value(json.clone())
+        .map_err(|_| "unexpected format error with script controller")?;
+
+    let source = match (config.script, config.script_file) {
+        (Some(script), _) => script,
+        (None, Some(file)) => fs::read_to_string(file).map_err(|_| "failed to read script file")?,
+        (None, None) => return Err("script controller must give script or script_file"),
+    };
+
+    Ok(Box::new(Script::new(config.address, config.args, context.region, &source, config.max_operations)?))
+}
+
+/// OSC argument for script value, anything other than a number or bool is sent as a string.
+/// Integers are clamped to the range of an OSC int
+fn osc_arg(value: Dynamic) -> OscType {
+    if let Ok(i) = value.as_int() {
+        OscType::Int(i.max(i32::min_value() as rhai::INT).min(i32::max_value() as rhai::INT) as i32)
+    }
+    else if let Ok(f) = value.as_float() {
+        OscType::Float(f as f32)
+    }
+    else if let Ok(b) = value.as_bool() {
+        OscType::Bool(b)
+    }
+    else {
+        OscType::String(value.to_string())
+    }
+}
+
+/// Script controller
+///  Touch events are passed to functions of a Rhai script, run with an operation budget
+/// so that a runaway script is stopped, rather than stalling the scan loop
+pub struct Script {
+    /// area of Morph covered by controller
+    region: Region,
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    /// functions script defines
+    functions: HashSet<String>,
+    /// persistent state, bound to this
+    state: Dynamic,
+    /// messages emitted by the script, sent once a call returns
+    outbox: Rc<RefCell<Vec<OscMessage>>>,
+    twist: Twist,
+}
+
+impl Script {
+    pub fn new(
+        address: String, args: Vec<ArgType>, region: Region,
+        source: &str, max_operations: Option<u64>) -> Result<Self, &'static str> {
+        let args: Vec<OscType> = args.into_iter().map(|a| OscType::from(a)).collect();
+        let outbox = Rc::new(RefCell::new(vec![]));
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(max_operations.unwrap_or(MAX_OPERATIONS));
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        engine.set_max_string_size(MAX_SIZE);
+        engine.set_max_array_size(MAX_SIZE);
+        engine.set_max_map_size(MAX_SIZE);
+
+        // print and debug go to the log, rather than stdout
+        engine.on_print(|text| info!("script: {}", text));
+        engine.on_debug(|text, _, pos| debug!("script {}: {}", pos, text));
+
+        let emit_outbox = outbox.clone();
+        engine.register_fn("emit", move |values: Array| {
+            let mut msg_args = args.clone();
+            msg_args.extend(values.into_iter().map(osc_arg));
+            emit_outbox.borrow_mut().push(OscMessage {
+                addr: address.clone(),
+                args: Some(msg_args),
+            });
+        });
+        let emit_outbox = outbox.clone();
+        engine.register_fn("emit_to", move |addr: &str, values: Array| {
+            emit_outbox.borrow_mut().push(OscMessage {
+                addr: addr.to_string(),
+                args: Some(values.into_iter().map(osc_arg).collect()),
+            });
+        });
+
+        let ast = engine.compile(source).map_err(|e| {
+            error!("script: {}", e);
+            "failed to compile script"
+        })?;
+        let functions = ast.iter_functions().map(|f| f.name.to_string()).collect();
+
+        let mut script = Script {
+            region: region,
+            engine: engine,
+            ast: ast,
+            scope: Scope::new(),
+            functions: functions,
+            state: Dynamic::from_map(Map::new()),
+            outbox: outbox,
+            twist: Twist::new(),
+        };
+
+        // init may set up this, or return the initial state as a map
+        let state = script.call("init", vec![])?;
+        if state.is_map() {
+            script.state = state;
+        }
+        Ok(script)
+    }
+
+    /// contact as a script map
+    fn contact(&mut self, contact: &contact::TrackedContact) -> Dynamic {
+        let twist = self.twist.update(contact);
+        let mut map = Map::new();
+        let mut insert = |k: &str, v: f32| { map.insert(k.into(), Dynamic::from_float(v as rhai::FLOAT)); };
+        insert("x", contact.x);
+        insert("y", contact.y);
+        insert("x_norm", self.region.norm_x(contact.x));
+        insert("y_norm", self.region.norm_y(contact.y));
+        insert("force", contact.total_force);
+        insert("area", contact.area);
+        insert("orientation", contact.ellipse.map_or(0.0, |e| e.orientation));
+        insert("major_axis", contact.ellipse.map_or(0.0, |e| e.major_axis));
+        insert("minor_axis", contact.ellipse.map_or(0.0, |e| e.minor_axis));
+        insert("delta_x", contact.delta.map_or(0.0, |d| d.x));
+        insert("delta_y", contact.delta.map_or(0.0, |d| d.y));
+        insert("delta_force", contact.delta.map_or(0.0, |d| d.force));
+        insert("twist", twist);
+        insert("flatness", expression::flatness(contact));
+        insert("velocity_x", contact.velocity_x);
+        insert("velocity_y", contact.velocity_y);
+        insert("speed", contact.speed());
+        insert("distance", contact.distance);
+        insert("duration_ms", contact.duration.as_millis() as f32);
+        map.insert("id".into(), Dynamic::from_int(contact.id as rhai::INT));
+        Dynamic::from_map(map)
+    }
+
+    /// call script function, if defined
+    fn call(&mut self, name: &str, args: Vec<Dynamic>) -> Result<Dynamic, &'static str> {
+        if !self.functions.contains(name) {
+            return Ok(Dynamic::UNIT);
+        }
+
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut self.state);
+        self.engine.call_fn_with_options::<Dynamic>(options, &mut self.scope, &self.ast, name, args)
+            .map_err(|e| {
+                error!("script {}: {}", name, e);
+                "script failed"
+            })
+    }
+
+    /// send messages emitted by script
+    fn flush(&self, transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        for msg in self.outbox.borrow_mut().drain(..) {
+            transport.send((OscPacket::Message(msg), None)).unwrap();
+        }
+    }
+}
+
+impl Controller for Script {
+    fn name(&self) -> &'static str {
+        "script"
+    }
+
+    fn touch_start(&mut self,
+             contact: &contact::TrackedContact,
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        let name = match contact.state {
+            contact::State::CONTACT_START => "start",
+            contact::State::CONTACT_MOVE => "move",
+            contact::State::CONTACT_END => "end",
+            _ => return Ok(()),
+        };
+
+        let arg = self.contact(contact);
+        let result = self.call(name, vec![arg]);
+        // messages emitted before any failure are still sent
+        self.flush(transport);
+        result.map(|_| ())
+    }
+
+    fn touch_move(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn touch_end(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+
+        self.touch_start(contact, transport).is_ok()
+    }
+
+    fn tick(&mut self,
+        _now: Instant,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        if self.call("tick", vec![]).is_err() {
+            // a failing tick would fail, and be logged, every loop
+            error!("script tick disabled");
+            self.functions.remove("tick");
+        }
+        self.flush(transport);
+    }
+
+    /// scripts may read any field of a contact
+    fn contact_mask(&self) -> contact::Mask {
+        contact::Mask::ELLIPSE | contact::Mask::DELTAS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn script(source: &str) -> Script {
+        let region = Region { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+        Script::new("/script".to_string(), vec![], region, source, None).unwrap()
+    }
+
+    #[test]
+    fn init_sets_up_this() {
+        let (tx, rx) = channel();
+        let mut script = script("fn init() { this.count = 1; } fn tick() { emit([this.count]); }");
+        script.tick(Instant::now(), &tx);
+        match rx.try_recv() {
+            Ok((OscPacket::Message(msg), _)) => assert_eq!(msg.args, Some(vec![OscType::Int(1)])),
+            _ => panic!("expected message"),
+        }
+    }
+
+    #[test]
+    fn init_returns_state() {
+        let (tx, rx) = channel();
+        let mut script = script("fn init() { #{ count: 2 } } fn tick() { emit([this.count]); }");
+        script.tick(Instant::now(), &tx);
+        match rx.try_recv() {
+            Ok((OscPacket::Message(msg), _)) => assert_eq!(msg.args, Some(vec![OscType::Int(2)])),
+            _ => panic!("expected message"),
+        }
+    }
+
+    #[test]
+    fn failing_tick_is_disabled() {
+        let (tx, rx) = channel();
+        let mut script = script("fn tick() { emit([1]); this.missing.field }");
+        script.tick(Instant::now(), &tx);
+        script.tick(Instant::now(), &tx);
+        assert_eq!(rx.try_iter().count(), 1);
+    }
+
+    #[test]
+    fn endless_script_is_stopped() {
+        let (tx, _rx) = channel();
+        let mut script = script("fn start(c) { loop {} }");
+        let contact = contact::TrackedContact::from(contact::Contact::at(1, contact::State::CONTACT_START, 10.0, 10.0));
+        assert!(script.touch_start(&contact, &tx).is_err());
+    }
+
+    #[test]
+    fn large_int_is_clamped() {
+        let (tx, rx) = channel();
+        let mut script = script("fn tick() { emit([5000000000, -5000000000]); }");
+        script.tick(Instant::now(), &tx);
+        match rx.try_recv() {
+            Ok((OscPacket::Message(msg), _)) =>
+                assert_eq!(msg.args, Some(vec![OscType::Int(i32::max_value()), OscType::Int(i32::min_value())])),
+            _ => panic!("expected message"),
+        }
+    }
+}
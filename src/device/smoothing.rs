@@ -0,0 +1,395 @@
+//! Description:
+//!    Smoothing of contact position and force, removing the jitter of raw
+//!    contacts, e.g. that makes a relative slider twitch at rest, before
+//!    they reach a controller
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use std::net::{SocketAddrV4};
+use std::sync::mpsc::{Sender};
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::PI;
+use std::time::Instant;
+
+use rosc::{OscPacket, OscMessage};
+
+use serde_json::Value;
+
+use crate::sensel::*;
+
+use super::controllers::*;
+
+/// Smoothing as given in the JSON IR, either for the whole layout or for a single
+/// controller, applied on top of any layout wide smoothing
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmoothingConfig {
+    /// none, moving_average, exponential, one_euro or kalman
+    pub filter: String,
+    /// samples averaged by moving average (default 4)
+    pub window: Option<usize>,
+    /// weight of each new sample for exponential, above 0 and at most 1 (default 0.5)
+    pub alpha: Option<f32>,
+    /// One Euro cutoff, in Hz, at rest, above 0 (default 1)
+    pub min_cutoff: Option<f32>,
+    /// One Euro increase in cutoff with speed (default 0.007)
+    pub beta: Option<f32>,
+    /// One Euro cutoff, in Hz, for speed, above 0 (default 1)
+    pub d_cutoff: Option<f32>,
+    /// Kalman variance of true value between samples (default 0.01)
+    pub process_noise: Option<f32>,
+    /// Kalman variance of samples, above 0 (default 1)
+    pub measurement_noise: Option<f32>,
+    /// smooth force, as well as position (default true)
+    pub force: Option<bool>,
+}
+
+/// Filter type, with its parameters
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    MovingAverage { window: usize },
+    Exponential { alpha: f32 },
+    OneEuro { min_cutoff: f32, beta: f32, d_cutoff: f32 },
+    Kalman { q: f32, r: f32 },
+}
+
+/// Filter of a single value, e.g. a contact's x position
+#[derive(Debug, Clone)]
+enum Filter {
+    MovingAverage { window: usize, values: VecDeque<f32> },
+    Exponential { alpha: f32, value: Option<f32> },
+    OneEuro { min_cutoff: f32, beta: f32, d_cutoff: f32, last: Option<(f32, f32, Instant)> },
+    Kalman { q: f32, r: f32, estimate: Option<(f32, f32)> },
+}
+
+/// smoothing factor of exponential filter with cutoff, in Hz, for sample period dt
+fn alpha(cutoff: f32, dt: f32) -> f32 {
+    let tau = 1.0 / (2.0 * PI * cutoff);
+    1.0 / (1.0 + tau / dt)
+}
+
+impl Filter {
+    fn new(kind: Kind) -> Self {
+        match kind {
+            Kind::MovingAverage { window } => Filter::MovingAverage { window: window, values: VecDeque::new() },
+            Kind::Exponential { alpha } => Filter::Exponential { alpha: alpha, value: None },
+            Kind::OneEuro { min_cutoff, beta, d_cutoff } =>
+                Filter::OneEuro { min_cutoff: min_cutoff, beta: beta, d_cutoff: d_cutoff, last: None },
+            Kind::Kalman { q, r } => Filter::Kalman { q: q, r: r, estimate: None },
+        }
+    }
+
+    /// filtered value for sample v, taken at time now
+    fn filter(&mut self, v: f32, now: Instant) -> f32 {
+        match self {
+            Filter::MovingAverage { window, values } => {
+                if values.len() == *window {
+                    values.pop_front();
+                }
+                values.push_back(v);
+                values.iter().sum::<f32>() / values.len() as f32
+            },
+            Filter::Exponential { alpha, value } => {
+                let filtered = value.map_or(v, |last| last + *alpha * (v - last));
+                *value = Some(filtered);
+                filtered
+            },
+            Filter::OneEuro { min_cutoff, beta, d_cutoff, last } => {
+                let filtered = match *last {
+                    Some((x, dx, time)) => {
+                        let dt = now.duration_since(time).as_micros() as f32 / 1_000_000.0;
+                        if dt <= 0.0 {
+                            return x;
+                        }
+                        // cutoff rises with speed, so slow movement is smoothed while fast
+                        // movement keeps up
+                        let dx = dx + alpha(*d_cutoff, dt) * ((v - x) / dt - dx);
+                        let cutoff = *min_cutoff + *beta * dx.abs();
+                        let x = x + alpha(cutoff, dt) * (v - x);
+                        (x, dx, now)
+                    },
+                    None => (v, 0.0, now),
+                };
+                *last = Some(filtered);
+                filtered.0
+            },
+            Filter::Kalman { q, r, estimate } => {
+                let (x, p) = match *estimate {
+                    Some((x, p)) => {
+                        let p = p + *q;
+                        let k = p / (p + *r);
+                        (x + k * (v - x), (1.0 - k) * p)
+                    },
+                    None => (v, *r),
+                };
+                *estimate = Some((x, p));
+                x
+            },
+        }
+    }
+}
+
+/// Filters of a single contact
+#[derive(Debug, Clone)]
+struct Filters {
+    x: Filter,
+    y: Filter,
+    force: Option<Filter>,
+}
+
+/// Smoothing of each contact, by id, from start to end
+#[derive(Debug, Clone)]
+pub struct Smoother {
+    kind: Kind,
+    force: bool,
+    contacts: HashMap<u8, Filters>,
+}
+
+impl Smoother {
+    /// smoother for config, None if config is for no smoothing
+    pub fn new(config: &SmoothingConfig) -> Result<Option<Self>, &'static str> {
+        let kind = match config.filter.as_str() {
+            "none" => return Ok(None),
+            "moving_average" => Kind::MovingAverage { window: config.window.unwrap_or(4).max(1) },
+            "exponential" => Kind::Exponential { alpha: config.alpha.unwrap_or(0.5) },
+            "one_euro" => Kind::OneEuro {
+                min_cutoff: config.min_cutoff.unwrap_or(1.0),
+                beta: config.beta.unwrap_or(0.007),
+                d_cutoff: config.d_cutoff.unwrap_or(1.0),
+            },
+            "kalman" => Kind::Kalman {
+                q: config.process_noise.unwrap_or(0.01),
+                r: config.measurement_noise.unwrap_or(1.0),
+            },
+            _ => return Err("unknown smoothing filter, expected none, moving_average, exponential, one_euro or kalman"),
+        };
+
+        // parameters that would stop the filter following, or make it diverge
+        match kind {
+            Kind::Exponential { alpha } if !(alpha > 0.0 && alpha <= 1.0) =>
+                return Err("exponential smoothing alpha must be above 0 and at most 1"),
+            Kind::OneEuro { min_cutoff, beta, d_cutoff } if !(min_cutoff > 0.0 && beta >= 0.0 && d_cutoff > 0.0) =>
+                return Err("one euro smoothing cutoffs must be above 0, and beta not negative"),
+            Kind::Kalman { q, r } if !(q >= 0.0 && r > 0.0) =>
+                return Err("kalman smoothing process noise must not be negative, and measurement noise must be above 0"),
+            _ => { },
+        }
+
+        Ok(Some(Smoother {
+            kind: kind,
+            force: config.force.unwrap_or(true),
+            contacts: HashMap::new(),
+        }))
+    }
+
+    /// smooth position, and force, of contact, each contact is smoothed from its start
+    pub fn smooth(&mut self, contact: &mut contact::Contact, now: Instant) {
+        if contact.state == contact::State::CONTACT_START {
+            self.contacts.remove(&contact.id);
+        }
+
+        let (kind, force) = (self.kind, self.force);
+        let filters = self.contacts.entry(contact.id).or_insert_with(|| Filters {
+            x: Filter::new(kind),
+            y: Filter::new(kind),
+            force: if force { Some(Filter::new(kind)) } else { None },
+        });
+
+        contact.x = filters.x.filter(contact.x, now);
+        contact.y = filters.y.filter(contact.y, now);
+        if let Some(filter) = &mut filters.force {
+            contact.total_force = filter.filter(contact.total_force, now);
+        }
+
+        if contact.state == contact::State::CONTACT_END || contact.state == contact::State::CONTACT_INVALID {
+            self.contacts.remove(&contact.id);
+        }
+    }
+}
+
+/// layout wide smoothing, applied to every contact before it is tracked and
+/// dispatched, if the layout gives any
+pub fn layout(obj: &serde_json::Map<String, Value>) -> Result<Option<Smoother>, &'static str> {
+    match obj.get("smoothing") {
+        Some(config) => {
+            let config: SmoothingConfig = serde_json::from_value(config.clone())
+                .map_err(|_| "unexpected format error with smoothing")?;
+            Smoother::new(&config)
+        },
+        None => Ok(None),
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// Wraps a controller, smoothing the contacts passed to it. Smoothed contacts are
+/// tracked again, so their history, and so velocity, follows the smoothed position
+pub struct Smoothed {
+    inner: Box<Controller>,
+    smoother: Smoother,
+    tracker: contact::ContactTracker,
+}
+
+impl Smoothed {
+    pub fn new(inner: Box<Controller>, smoother: Smoother) -> Self {
+        Smoothed {
+            inner: inner,
+            smoother: smoother,
+            tracker: contact::ContactTracker::new(),
+        }
+    }
+
+    /// contact smoothed, at the time it was received, and tracked
+    fn smooth<'a>(
+        smoother: &mut Smoother,
+        tracker: &'a mut contact::ContactTracker,
+        contact: &contact::TrackedContact) -> &'a contact::TrackedContact {
+        let now = contact.history.back().map_or_else(Instant::now, |sample| sample.time);
+        let mut smoothed = **contact;
+        smoother.smooth(&mut smoothed, now);
+        tracker.update(smoothed, now)
+    }
+}
+
+impl Controller for Smoothed {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn touch_start(&mut self,
+             contact: &contact::TrackedContact,
+             transport: &Sender<(OscPacket, Option<SocketAddrV4>)>)
+                -> Result<(), &'static str> {
+        let contact = Smoothed::smooth(&mut self.smoother, &mut self.tracker, contact);
+        self.inner.touch_start(contact, transport)
+    }
+
+    fn touch_move(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let contact = Smoothed::smooth(&mut self.smoother, &mut self.tracker, contact);
+        self.inner.touch_move(contact, transport)
+    }
+
+    fn touch_end(&mut self,
+        contact: &contact::TrackedContact,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        let contact = Smoothed::smooth(&mut self.smoother, &mut self.tracker, contact);
+        self.inner.touch_end(contact, transport)
+    }
+
+    fn tick(&mut self,
+        now: Instant,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) {
+        self.inner.tick(now, transport)
+    }
+
+    fn osc_message(&mut self,
+        msg: &OscMessage,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        self.inner.osc_message(msg, transport)
+    }
+
+    fn state(&self) -> Option<Value> {
+        self.inner.state()
+    }
+
+    fn set_state(&mut self,
+        state: &Value,
+        transport: &Sender<(OscPacket, Option<SocketAddrV4>)>) -> bool {
+        self.inner.set_state(state, transport)
+    }
+
+    fn contact_mask(&self) -> contact::Mask {
+        self.inner.contact_mask()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn config(filter: &str) -> SmoothingConfig {
+        SmoothingConfig {
+            filter: filter.to_string(),
+            window: None,
+            alpha: None,
+            min_cutoff: None,
+            beta: None,
+            d_cutoff: None,
+            process_noise: None,
+            measurement_noise: None,
+            force: None,
+        }
+    }
+
+    /// filter samples, taken 10ms apart
+    fn filter(kind: Kind, samples: &[f32]) -> Vec<f32> {
+        let mut filter = Filter::new(kind);
+        let start = Instant::now();
+        samples.iter().enumerate()
+            .map(|(i, &v)| filter.filter(v, start + Duration::from_millis(10 * i as u64)))
+            .collect()
+    }
+
+    #[test]
+    fn moving_average() {
+        let filtered = filter(Kind::MovingAverage { window: 2 }, &[10.0, 20.0, 40.0]);
+        assert_eq!(filtered, vec![10.0, 15.0, 30.0]);
+    }
+
+    #[test]
+    fn exponential() {
+        let filtered = filter(Kind::Exponential { alpha: 0.5 }, &[10.0, 20.0, 20.0]);
+        assert_eq!(filtered, vec![10.0, 15.0, 17.5]);
+    }
+
+    #[test]
+    fn one_euro_follows_fast_movement_more_closely() {
+        let slow = filter(Kind::OneEuro { min_cutoff: 1.0, beta: 0.0, d_cutoff: 1.0 }, &[0.0, 10.0]);
+        let fast = filter(Kind::OneEuro { min_cutoff: 1.0, beta: 1.0, d_cutoff: 1.0 }, &[0.0, 10.0]);
+        assert_eq!(slow[0], 0.0);
+        assert!(slow[1] > 0.0 && slow[1] < fast[1] && fast[1] < 10.0);
+    }
+
+    #[test]
+    fn kalman_converges_on_steady_value() {
+        let mut samples = vec![0.0];
+        samples.extend(std::iter::repeat(10.0).take(50));
+        let filtered = filter(Kind::Kalman { q: 0.01, r: 1.0 }, &samples);
+        assert!(filtered[1] > 0.0 && filtered[1] < 10.0);
+        assert!((filtered[50] - 10.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn contacts_smoothed_from_start() {
+        let mut smoother = Smoother::new(&config("moving_average")).unwrap().unwrap();
+        let now = Instant::now();
+        let mut contact = contact::Contact::at(1, contact::State::CONTACT_START, 10.0, 10.0);
+        smoother.smooth(&mut contact, now);
+        contact.state = contact::State::CONTACT_MOVE;
+        contact.x = 20.0;
+        smoother.smooth(&mut contact, now);
+        assert_eq!(contact.x, 15.0);
+
+        // a new contact with the same id isn't averaged with the last
+        contact.state = contact::State::CONTACT_START;
+        contact.x = 50.0;
+        smoother.smooth(&mut contact, now);
+        assert_eq!(contact.x, 50.0);
+    }
+
+    #[test]
+    fn rejects_parameters_that_stop_or_diverge() {
+        assert!(Smoother::new(&config("none")).unwrap().is_none());
+        assert!(Smoother::new(&config("median")).is_err());
+        assert!(Smoother::new(&SmoothingConfig { alpha: Some(0.0), ..config("exponential") }).is_err());
+        assert!(Smoother::new(&SmoothingConfig { alpha: Some(1.5), ..config("exponential") }).is_err());
+        assert!(Smoother::new(&SmoothingConfig { min_cutoff: Some(0.0), ..config("one_euro") }).is_err());
+        assert!(Smoother::new(&SmoothingConfig { d_cutoff: Some(-1.0), ..config("one_euro") }).is_err());
+        assert!(Smoother::new(&SmoothingConfig { process_noise: Some(-0.1), ..config("kalman") }).is_err());
+        assert!(Smoother::new(&SmoothingConfig { measurement_noise: Some(0.0), ..config("kalman") }).is_err());
+        assert!(Smoother::new(&SmoothingConfig { process_noise: Some(0.0), ..config("kalman") }).unwrap().is_some());
+    }
+}
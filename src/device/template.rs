@@ -0,0 +1,211 @@
+//! Description:
+//!    Argument templates, describing which contact fields appear in a message
+//!    and in what order, e.g. ["$phase", "$id", "$x_norm", "$y_norm", "$force", 42]
+//!
+//! Copyright © 2019 Benedict Gaster. All rights reserved.
+//!
+
+use rosc::OscType;
+
+use crate::sensel::*;
+
+use super::controllers::Region;
+use super::expression::{self, Twist};
+
+/// An argument of a template, as given in the JSON IR, either a literal or a
+/// string, where strings starting with $ name a contact field
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TemplateArg {
+    Int(i32),
+    Float(f32),
+    Str(String),
+}
+
+/// Value placed in a message by a template
+#[derive(Debug, Clone, PartialEq)]
+enum Field {
+    Literal(OscType),
+    /// the controller's static arguments
+    Args,
+    /// a value computed by the controller, e.g. a macro target's mapped value
+    Value,
+    /// 0 start, 1 move, 2 end
+    Phase,
+    Id,
+    X,
+    Y,
+    /// positions relative to the controller's region, 0..1
+    XNorm,
+    YNorm,
+    Force,
+    Area,
+    Orientation,
+    MajorAxis,
+    MinorAxis,
+    DeltaX,
+    DeltaY,
+    DeltaForce,
+    DeltaArea,
+    MinX,
+    MinY,
+    MaxX,
+    MaxY,
+    PeakX,
+    PeakY,
+    PeakForce,
+    /// degrees turned since touch start
+    Twist,
+    /// minor over major axis, 0..1
+    Flatness,
+}
+
+impl Field {
+    fn new(arg: TemplateArg) -> Result<Self, &'static str> {
+        match arg {
+            TemplateArg::Int(i) => Ok(Field::Literal(OscType::Int(i))),
+            TemplateArg::Float(f) => Ok(Field::Literal(OscType::Float(f))),
+            TemplateArg::Str(s) => {
+                if !s.starts_with('$') {
+                    return Ok(Field::Literal(OscType::String(s)));
+                }
+                match &s[1..] {
+                    "args" => Ok(Field::Args),
+                    "value" => Ok(Field::Value),
+                    "phase" => Ok(Field::Phase),
+                    "id" => Ok(Field::Id),
+                    "x" => Ok(Field::X),
+                    "y" => Ok(Field::Y),
+                    "x_norm" => Ok(Field::XNorm),
+                    "y_norm" => Ok(Field::YNorm),
+                    "force" => Ok(Field::Force),
+                    "area" => Ok(Field::Area),
+                    "orientation" => Ok(Field::Orientation),
+                    "major_axis" => Ok(Field::MajorAxis),
+                    "minor_axis" => Ok(Field::MinorAxis),
+                    "delta_x" => Ok(Field::DeltaX),
+                    "delta_y" => Ok(Field::DeltaY),
+                    "delta_force" => Ok(Field::DeltaForce),
+                    "delta_area" => Ok(Field::DeltaArea),
+                    "min_x" => Ok(Field::MinX),
+                    "min_y" => Ok(Field::MinY),
+                    "max_x" => Ok(Field::MaxX),
+                    "max_y" => Ok(Field::MaxY),
+                    "peak_x" => Ok(Field::PeakX),
+                    "peak_y" => Ok(Field::PeakY),
+                    "peak_force" => Ok(Field::PeakForce),
+                    "twist" => Ok(Field::Twist),
+                    "flatness" => Ok(Field::Flatness),
+                    _ => Err("unknown template placeholder"),
+                }
+            }
+        }
+    }
+
+    /// contact data field needs enabled
+    fn mask(&self) -> contact::Mask {
+        match self {
+            Field::Orientation | Field::MajorAxis | Field::MinorAxis |
+            Field::Twist | Field::Flatness => contact::Mask::ELLIPSE,
+            Field::DeltaX | Field::DeltaY | Field::DeltaForce | Field::DeltaArea => contact::Mask::DELTAS,
+            Field::MinX | Field::MinY | Field::MaxX | Field::MaxY => contact::Mask::BOUNDING_BOX,
+            Field::PeakX | Field::PeakY | Field::PeakForce => contact::Mask::PEAK,
+            _ => contact::Mask::empty(),
+        }
+    }
+}
+
+/// Compiled argument template
+#[derive(Debug, Clone)]
+pub struct Template {
+    fields: Vec<Field>,
+    /// twist of contacts, tracked only if template uses it
+    twist: Twist,
+}
+
+impl Template {
+    pub fn new(args: Vec<TemplateArg>) -> Result<Self, &'static str> {
+        let mut fields = vec![];
+        for arg in args {
+            fields.push(Field::new(arg)?);
+        }
+        Ok(Template {
+            fields: fields,
+            twist: Twist::new(),
+        })
+    }
+
+    /// template from placeholder names, which must be valid
+    pub fn from_placeholders(placeholders: &[&str]) -> Self {
+        Template::new(placeholders.iter().map(|p| TemplateArg::Str(p.to_string())).collect())
+            .expect("invalid built in template")
+    }
+
+    /// contact data that must be enabled for every field of template
+    pub fn mask(&self) -> contact::Mask {
+        self.fields.iter().fold(contact::Mask::empty(), |mask, field| mask | field.mask())
+    }
+
+    /// build message arguments for contact, any fields not enabled for the contact
+    /// are sent as 0
+    pub fn build(
+        &mut self,
+        contact: &contact::Contact,
+        region: &Region,
+        args: &Vec<OscType>,
+        value: f32) -> Vec<OscType> {
+        let twist = if self.fields.contains(&Field::Twist) { self.twist.update(contact) } else { 0.0 };
+
+        let mut out = vec![];
+        for field in &self.fields {
+            let f = match field {
+                Field::Literal(l) => {
+                    out.push(l.clone());
+                    continue;
+                },
+                Field::Args => {
+                    out.extend(args.iter().cloned());
+                    continue;
+                },
+                Field::Phase => {
+                    out.push(OscType::Int(match contact.state {
+                        contact::State::CONTACT_START => 0,
+                        contact::State::CONTACT_MOVE => 1,
+                        contact::State::CONTACT_END => 2,
+                        _ => -1,
+                    }));
+                    continue;
+                },
+                Field::Id => {
+                    out.push(OscType::Int(contact.id as i32));
+                    continue;
+                },
+                Field::Value => value,
+                Field::X => contact.x,
+                Field::Y => contact.y,
+                Field::XNorm => region.norm_x(contact.x),
+                Field::YNorm => region.norm_y(contact.y),
+                Field::Force => contact.total_force,
+                Field::Area => contact.area,
+                Field::Orientation => contact.ellipse.map_or(0.0, |e| e.orientation),
+                Field::MajorAxis => contact.ellipse.map_or(0.0, |e| e.major_axis),
+                Field::MinorAxis => contact.ellipse.map_or(0.0, |e| e.minor_axis),
+                Field::DeltaX => contact.delta.map_or(0.0, |d| d.x),
+                Field::DeltaY => contact.delta.map_or(0.0, |d| d.y),
+                Field::DeltaForce => contact.delta.map_or(0.0, |d| d.force),
+                Field::DeltaArea => contact.delta.map_or(0.0, |d| d.area),
+                Field::MinX => contact.bounding_box.map_or(0.0, |b| b.min_x),
+                Field::MinY => contact.bounding_box.map_or(0.0, |b| b.min_y),
+                Field::MaxX => contact.bounding_box.map_or(0.0, |b| b.max_x),
+                Field::MaxY => contact.bounding_box.map_or(0.0, |b| b.max_y),
+                Field::PeakX => contact.peak.map_or(0.0, |p| p.x),
+                Field::PeakY => contact.peak.map_or(0.0, |p| p.y),
+                Field::PeakForce => contact.peak.map_or(0.0, |p| p.force),
+                Field::Twist => twist,
+                Field::Flatness => expression::flatness(contact),
+            };
+            out.push(OscType::Float(f));
+        }
+        out
+    }
+}
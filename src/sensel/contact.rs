@@ -1,3 +1,9 @@
+use std::collections::{HashMap, VecDeque};
+use std::collections::hash_map::Entry;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
 use super::bindings::*;
 
 pub use super::bindings::SenselContactState as State;
@@ -156,3 +162,280 @@ impl From<SenselContact> for Contact {
         }
     }
 }
+
+#[cfg(test)]
+impl Contact {
+    /// contact at x, y, with moderate force and no optional data, for tests
+    pub fn at(id: u8, state: State, x: f32, y: f32) -> Self {
+        Contact {
+            id: id,
+            state: state,
+            x: x,
+            y: y,
+            total_force: 100.0,
+            area: 10.0,
+            ellipse: None,
+            delta: None,
+            bounding_box: None,
+            peak: None
+        }
+    }
+}
+
+//-----------------------------------------------------------------------------
+
+/// number of frames of history kept for each contact
+pub const HISTORY_LEN: usize = 16;
+
+/// Position and force of a contact at the time of a frame
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    pub x: f32,
+    pub y: f32,
+    pub force: f32,
+    pub time: Instant
+}
+
+/// A contact along with its history, as seen by a tracker. Dereferences to the
+/// contact of the current frame, so fields, e.g. x, are accessed directly
+#[derive(Clone, Debug)]
+pub struct TrackedContact {
+    contact: Contact,
+    /// samples, oldest first, ending with the current frame, shared so that cloning
+    /// a contact, e.g. to pass it on in another state, doesn't copy them
+    pub history: Rc<VecDeque<Sample>>,
+    /// velocity, in units per second
+    pub velocity_x: f32,
+    pub velocity_y: f32,
+    /// acceleration, in units per second squared
+    pub acceleration_x: f32,
+    pub acceleration_y: f32,
+    /// time since contact started
+    pub duration: Duration,
+    /// total distance travelled since contact started
+    pub distance: f32
+}
+
+impl TrackedContact {
+    /// speed, in units per second
+    pub fn speed(&self) -> f32 {
+        (self.velocity_x * self.velocity_x + self.velocity_y * self.velocity_y).sqrt()
+    }
+
+    /// position contact started at, or oldest still in history
+    pub fn origin(&self) -> (f32, f32) {
+        self.history.front().map_or((self.contact.x, self.contact.y), |s| (s.x, s.y))
+    }
+
+    /// contact as if in state, sharing its history
+    pub fn with_state(&self, state: State) -> TrackedContact {
+        let mut contact = self.clone();
+        contact.state = state;
+        contact
+    }
+
+    /// sample from the previous frame, if any
+    pub fn previous(&self) -> Option<&Sample> {
+        if self.history.len() > 1 {
+            self.history.get(self.history.len() - 2)
+        } else {
+            None
+        }
+    }
+}
+
+/// Contact without history, e.g. one not seen by a tracker
+impl From<Contact> for TrackedContact {
+    fn from(contact: Contact) -> Self {
+        let mut history = VecDeque::with_capacity(HISTORY_LEN);
+        history.push_back(Sample {
+            x: contact.x,
+            y: contact.y,
+            force: contact.total_force,
+            time: Instant::now()
+        });
+
+        TrackedContact {
+            contact,
+            history: Rc::new(history),
+            velocity_x: 0.0,
+            velocity_y: 0.0,
+            acceleration_x: 0.0,
+            acceleration_y: 0.0,
+            duration: Duration::from_millis(0),
+            distance: 0.0
+        }
+    }
+}
+
+impl Deref for TrackedContact {
+    type Target = Contact;
+
+    fn deref(&self) -> &Contact {
+        &self.contact
+    }
+}
+
+impl DerefMut for TrackedContact {
+    fn deref_mut(&mut self) -> &mut Contact {
+        &mut self.contact
+    }
+}
+
+/// Keeps the history of each contact, by id, from start to end, so that velocity,
+/// acceleration, duration and distance are available to controllers
+#[derive(Clone, Debug, Default)]
+pub struct ContactTracker {
+    contacts: HashMap<u8, TrackedContact>,
+    /// contact that ended on the last update, forgotten on the next
+    ended: Option<u8>
+}
+
+impl ContactTracker {
+    pub fn new() -> Self {
+        ContactTracker {
+            contacts: HashMap::new(),
+            ended: None
+        }
+    }
+
+    /// add contact, received at time now, to its history, returning it tracked, valid
+    /// until the next update
+    pub fn update(&mut self, contact: Contact, now: Instant) -> &TrackedContact {
+        if let Some(id) = self.ended.take() {
+            self.contacts.remove(&id);
+        }
+
+        let sample = Sample {
+            x: contact.x,
+            y: contact.y,
+            force: contact.total_force,
+            time: now
+        };
+
+        if contact.state == State::CONTACT_START {
+            self.contacts.remove(&contact.id);
+        }
+
+        if contact.state == State::CONTACT_END || contact.state == State::CONTACT_INVALID {
+            self.ended = Some(contact.id);
+        }
+
+        match self.contacts.entry(contact.id) {
+            Entry::Occupied(entry) => {
+                let tracked = entry.into_mut();
+                let last = *tracked.history.back().unwrap();
+                let dt = now.duration_since(last.time).as_micros() as f32 / 1_000_000.0;
+                let (dx, dy) = (sample.x - last.x, sample.y - last.y);
+
+                // frames arriving together keep the previous estimate
+                if dt > 0.0 {
+                    let (vx, vy) = (dx / dt, dy / dt);
+                    tracked.acceleration_x = (vx - tracked.velocity_x) / dt;
+                    tracked.acceleration_y = (vy - tracked.velocity_y) / dt;
+                    tracked.velocity_x = vx;
+                    tracked.velocity_y = vy;
+                }
+                tracked.distance += (dx * dx + dy * dy).sqrt();
+
+                // only copied if a clone of the contact is still held
+                let history = Rc::make_mut(&mut tracked.history);
+                if history.len() == HISTORY_LEN {
+                    history.pop_front();
+                }
+                history.push_back(sample);
+                tracked.duration += now.duration_since(last.time);
+                tracked.contact = contact;
+                tracked
+            },
+            Entry::Vacant(entry) => {
+                let tracked = entry.insert(TrackedContact::from(contact));
+                Rc::make_mut(&mut tracked.history)[0].time = now;
+                tracked
+            }
+        }
+    }
+
+    /// forget all contacts, e.g. when scanning restarts
+    pub fn clear(&mut self) {
+        self.contacts.clear();
+        self.ended = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_motion() {
+        let mut tracker = ContactTracker::new();
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        tracker.update(Contact::at(1, State::CONTACT_START, 10.0, 10.0), at(0));
+        // 30 right and 40 down in 100ms, i.e. 500 per second
+        let tracked = tracker.update(Contact::at(1, State::CONTACT_MOVE, 40.0, 50.0), at(100));
+        assert_eq!((tracked.velocity_x, tracked.velocity_y), (300.0, 400.0));
+        assert_eq!(tracked.speed(), 500.0);
+        assert_eq!(tracked.distance, 50.0);
+        assert_eq!(tracked.previous().map(|s| (s.x, s.y)), Some((10.0, 10.0)));
+
+        // velocity doubling over the next 100ms
+        let tracked = tracker.update(Contact::at(1, State::CONTACT_MOVE, 100.0, 130.0), at(200));
+        assert_eq!((tracked.velocity_x, tracked.velocity_y), (600.0, 800.0));
+        assert_eq!((tracked.acceleration_x, tracked.acceleration_y), (3000.0, 4000.0));
+        assert_eq!(tracked.distance, 150.0);
+        assert_eq!(tracked.duration, Duration::from_millis(200));
+        assert_eq!(tracked.origin(), (10.0, 10.0));
+    }
+
+    #[test]
+    fn frames_arriving_together_keep_velocity() {
+        let mut tracker = ContactTracker::new();
+        let start = Instant::now();
+
+        tracker.update(Contact::at(1, State::CONTACT_START, 10.0, 10.0), start);
+        tracker.update(Contact::at(1, State::CONTACT_MOVE, 20.0, 10.0), start + Duration::from_millis(10));
+        let tracked = tracker.update(Contact::at(1, State::CONTACT_MOVE, 30.0, 10.0), start + Duration::from_millis(10));
+        assert_eq!(tracked.velocity_x, 1000.0);
+        assert_eq!(tracked.distance, 20.0);
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut tracker = ContactTracker::new();
+        let start = Instant::now();
+
+        tracker.update(Contact::at(1, State::CONTACT_START, 0.0, 0.0), start);
+        for i in 1..HISTORY_LEN * 2 {
+            tracker.update(Contact::at(1, State::CONTACT_MOVE, i as f32, 0.0), start + Duration::from_millis(i as u64));
+        }
+        let tracked = tracker.update(Contact::at(1, State::CONTACT_MOVE, 100.0, 0.0), start + Duration::from_secs(1));
+        assert_eq!(tracked.history.len(), HISTORY_LEN);
+    }
+
+    #[test]
+    fn contact_in_another_state_shares_history() {
+        let mut tracker = ContactTracker::new();
+        let tracked = tracker.update(Contact::at(1, State::CONTACT_START, 10.0, 10.0), Instant::now());
+        let end = tracked.with_state(State::CONTACT_END);
+        assert_eq!(end.state, State::CONTACT_END);
+        assert!(Rc::ptr_eq(&end.history, &tracked.history));
+    }
+
+    #[test]
+    fn ended_contact_is_forgotten() {
+        let mut tracker = ContactTracker::new();
+        let start = Instant::now();
+
+        tracker.update(Contact::at(1, State::CONTACT_START, 10.0, 10.0), start);
+        let tracked = tracker.update(Contact::at(1, State::CONTACT_END, 20.0, 10.0), start + Duration::from_millis(10));
+        assert_eq!(tracked.distance, 10.0);
+
+        // id reused, e.g. by a move seen without its start
+        let tracked = tracker.update(Contact::at(1, State::CONTACT_MOVE, 50.0, 10.0), start + Duration::from_millis(20));
+        assert_eq!(tracked.distance, 0.0);
+        assert!(tracked.previous().is_none());
+    }
+}